@@ -0,0 +1,441 @@
+//! This module provides adapters which normalize line endings of the data read from a reader or
+//! written into a writer, so the conversion can be composed with other I/O types.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::{BufRead, BufReader};
+//!
+//! use loe::{Config, Reader};
+//!
+//! let input = &b"hello\r\nworld!\r\n"[..];
+//! let reader = BufReader::new(Reader::new(input, Config::default()));
+//!
+//! let lines = reader.split(b'\n').collect::<Result<Vec<_>, _>>().unwrap();
+//! assert_eq!(lines, vec![b"hello".to_vec(), b"world!".to_vec()]);
+//! ```
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::converter::Converter;
+use crate::{Config, EncodingChecker, ParseError, Transform};
+
+/// Reader which converts the data read from the inner reader. The data are read in chunks of the
+/// buffer size set in the config.
+///
+/// Errors of invalid encoding are returned as I/O errors of `InvalidData` kind which wrap
+/// [ParseError](enum.ParseError.html). Once such error occurs, it is returned by all following
+/// reads.
+pub struct Reader<R> {
+    inner: R,
+    converter: Converter,
+    read_buffer: Vec<u8>,
+    /// Converted data which were not read yet.
+    converted: Vec<u8>,
+    pos: usize,
+    finished: bool,
+    /// The error which stopped the conversion.
+    error: Option<ParseError>,
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates the reader with given config.
+    pub fn new<E, T>(inner: R, config: Config<E, T>) -> Self
+    where
+        E: Into<Box<dyn EncodingChecker>> + fmt::Display,
+        T: Into<Box<dyn Transform>>,
+    {
+        Reader {
+            inner,
+            read_buffer: vec![0; config.buffer_size],
+            converter: Converter::new(config),
+            converted: Vec::new(),
+            pos: 0,
+            finished: false,
+            error: None,
+        }
+    }
+
+    /// Returns a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner reader. Reading from it directly corrupts the
+    /// conversion.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the inner reader. The data which were converted but not read yet are lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(ref err) = self.error {
+            return Err(err.duplicate().into());
+        }
+
+        while self.pos == self.converted.len() {
+            if self.finished || buf.is_empty() {
+                return Ok(0);
+            }
+
+            self.converted.clear();
+            self.pos = 0;
+
+            let n = self.inner.read(&mut self.read_buffer)?;
+            let result = if n == 0 {
+                self.finished = true;
+                self.converter.finish(&mut self.converted).map(|_| ())
+            } else {
+                self.converter
+                    .convert(&self.read_buffer[0..n], &mut self.converted)
+            };
+
+            if let Err(err) = result {
+                return Err(remember(&mut self.error, err));
+            }
+        }
+
+        let len = buf.len().min(self.converted.len() - self.pos);
+        buf[0..len].copy_from_slice(&self.converted[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Writer which converts the data before writing them into the inner writer.
+///
+/// The conversion must be completed by calling [finish](#method.finish), otherwise the end of the
+/// data may not be written. If the writer is dropped without finishing, it is done then and the
+/// errors are ignored.
+///
+/// Errors of invalid encoding are returned as I/O errors of `InvalidData` kind which wrap
+/// [ParseError](enum.ParseError.html). Once such error occurs, it is returned by all following
+/// writes and by finishing.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+///
+/// use loe::{Config, TransformMode, Writer};
+///
+/// let mut writer = Writer::new(Vec::new(), Config::default().transform(TransformMode::Crlf));
+/// writer.write_all(b"hello\n").unwrap();
+/// writer.write_all(b"world!\n").unwrap();
+///
+/// let output = writer.finish().unwrap();
+/// assert_eq!(output, b"hello\r\nworld!\r\n");
+/// ```
+pub struct Writer<W: Write> {
+    /// The inner writer. It is `None` only after finishing.
+    inner: Option<W>,
+    converter: Converter,
+    /// Converted data which were not written into the inner writer yet.
+    converted: Vec<u8>,
+    /// Number of bytes at the beginning of `converted` which were already written.
+    written: usize,
+    /// Whether the conversion was finished and only the rest of the data may need to be written.
+    finished: bool,
+    /// The error which stopped the conversion.
+    error: Option<ParseError>,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates the writer with given config.
+    pub fn new<E, T>(inner: W, config: Config<E, T>) -> Self
+    where
+        E: Into<Box<dyn EncodingChecker>> + fmt::Display,
+        T: Into<Box<dyn Transform>>,
+    {
+        Writer {
+            inner: Some(inner),
+            converter: Converter::new(config),
+            converted: Vec::new(),
+            written: 0,
+            finished: false,
+            error: None,
+        }
+    }
+
+    /// Returns a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Returns a mutable reference to the inner writer. Writing to it directly corrupts the
+    /// conversion.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Completes the conversion, flushes the inner writer and returns it.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.finish_conversion()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn finish_conversion(&mut self) -> io::Result<()> {
+        if let Some(ref err) = self.error {
+            return Err(err.duplicate().into());
+        }
+
+        self.write_converted()?;
+
+        if !self.finished {
+            self.finished = true;
+            if let Err(err) = self.converter.finish(&mut self.converted) {
+                return Err(remember(&mut self.error, err));
+            }
+
+            self.write_converted()?;
+        }
+
+        self.inner.as_mut().unwrap().flush()
+    }
+
+    /// Writes the converted data which were not written yet. If it fails, the rest is kept for the
+    /// next attempt.
+    fn write_converted(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+
+        while self.written < self.converted.len() {
+            match inner.write(&self.converted[self.written..]) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write the converted data",
+                    ))
+                }
+                Ok(n) => self.written += n,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.converted.clear();
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // the data from the previous call must be written first, otherwise this input is not
+        // accepted
+        if let Some(ref err) = self.error {
+            return Err(err.duplicate().into());
+        }
+
+        self.write_converted()?;
+        if let Err(err) = self.converter.convert(buf, &mut self.converted) {
+            return Err(remember(&mut self.error, err));
+        }
+
+        // the input is accepted at this point, so a failure is reported by the next call which
+        // writes the rest again
+        let _ = self.write_converted();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_converted()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for Writer<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.finish_conversion();
+        }
+    }
+}
+
+/// Remembers the error which stopped the conversion so that it is returned by all following calls.
+fn remember(slot: &mut Option<ParseError>, err: ParseError) -> io::Error {
+    *slot = Some(err.duplicate());
+    err.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bom, Encoding, TransformMode};
+
+    /// Reader which returns at most one byte at a time.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((byte, rest)), Some(first)) => {
+                    *first = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn reader() {
+        let mut reader = Reader::new(
+            Trickle(b"\xef\xbb\xbfhello\r\nworld!\r"),
+            Config::default().bom(Bom::Remove),
+        );
+
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"hello\nworld!\n");
+    }
+
+    #[test]
+    fn reader_small_reads() {
+        let mut reader = Reader::new(
+            &b"a\r\nb\r\nc"[..],
+            Config::default().transform(TransformMode::Cr),
+        );
+
+        let mut output = Vec::new();
+        let mut buf = [0; 1];
+        while reader.read(&mut buf).unwrap() == 1 {
+            output.push(buf[0]);
+        }
+
+        assert_eq!(output, b"a\rb\rc");
+    }
+
+    #[test]
+    fn reader_invalid_encoding() {
+        let mut reader = Reader::new(
+            &b"hello\r\nworld \xe2\x82"[..],
+            Config::default().encoding(Encoding::Utf8),
+        );
+
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = err.into_inner().unwrap().downcast::<ParseError>().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "2:7: incomplete UTF-8 sequence 0xe2 0x82 at the end of input"
+        );
+    }
+
+    #[test]
+    fn reader_error_repeated() {
+        let mut reader = Reader::new(
+            Trickle(b"ab\xffcd\n"),
+            Config::default().encoding(Encoding::Ascii),
+        );
+
+        let mut output = Vec::new();
+        let err = reader.read_to_end(&mut output).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(output, b"ab");
+
+        // the rest of the input is not read as if it ended cleanly
+        for _ in 0..2 {
+            let err = reader.read(&mut [0; 16]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            let err = err.into_inner().unwrap().downcast::<ParseError>().unwrap();
+            assert_eq!(err.to_string(), "1:3: invalid Ascii byte 0xff");
+        }
+    }
+
+    #[test]
+    fn writer() {
+        let mut writer = Writer::new(Vec::new(), Config::default().bom(Bom::Add));
+        for byte in b"hello\r\nworld!\r\n" {
+            writer.write_all(&[*byte]).unwrap();
+        }
+
+        assert_eq!(writer.finish().unwrap(), b"\xef\xbb\xbfhello\nworld!\n");
+    }
+
+    #[test]
+    fn writer_drop() {
+        let mut output = Vec::new();
+        {
+            // the byte order mark is not decided until the end
+            let mut writer = Writer::new(&mut output, Config::default().bom(Bom::Remove));
+            writer.write_all(b"\xef\xbb").unwrap();
+            assert!(writer.get_ref().is_empty());
+        }
+
+        assert_eq!(output, b"\xef\xbb");
+    }
+
+    /// Writer which fails every other call and otherwise writes at most two bytes.
+    struct Flaky {
+        written: Vec<u8>,
+        fail: bool,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.fail = !self.fail;
+            if self.fail {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+
+            let len = buf.len().min(2);
+            self.written.extend_from_slice(&buf[0..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writer_inner_error() {
+        let inner = Flaky {
+            written: Vec::new(),
+            fail: false,
+        };
+        let mut writer = Writer::new(inner, Config::default().transform(TransformMode::Crlf));
+
+        let mut input = &b"hello\nworld!\n"[..];
+        while !input.is_empty() {
+            match writer.write(&input[0..input.len().min(3)]) {
+                Ok(n) => input = &input[n..],
+                Err(err) => assert_eq!(err.kind(), io::ErrorKind::WouldBlock),
+            }
+        }
+
+        let inner = loop {
+            // the writer is dropped on error, so it is finished by flushing first
+            match writer.flush() {
+                Ok(()) => break writer.finish().unwrap(),
+                Err(err) => assert_eq!(err.kind(), io::ErrorKind::WouldBlock),
+            }
+        };
+
+        assert_eq!(inner.written, b"hello\r\nworld!\r\n");
+    }
+
+    #[test]
+    fn writer_invalid_encoding() {
+        let mut writer = Writer::new(Vec::new(), Config::default().encoding(Encoding::Ascii));
+        writer.write_all(b"hello\r\n").unwrap();
+
+        let err = writer.write_all("světe".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(writer.get_ref(), b"hello\n");
+
+        // the conversion does not continue after the error
+        let err = writer.write_all(b"world!\r\n").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(writer.get_ref(), b"hello\n");
+        assert!(writer.finish().is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+/// Writer which compares the written data with the content of the original instead of storing
+/// them anywhere.
+pub struct Compare<R: Read> {
+    original: R,
+    buffer: Vec<u8>,
+    differs: bool,
+}
+
+impl<R: Read> Compare<R> {
+    pub fn new(original: R) -> Self {
+        Compare {
+            original,
+            buffer: Vec::new(),
+            differs: false,
+        }
+    }
+
+    /// Returns true if the written data are not identical to the original.
+    pub fn differs(mut self) -> io::Result<bool> {
+        if self.differs {
+            return Ok(true);
+        }
+
+        // the original must not be longer than the written data
+        let mut rest = [0; 1];
+        loop {
+            match self.original.read(&mut rest) {
+                Ok(n) => return Ok(n > 0),
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl<R: Read> Write for Compare<R> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.differs {
+            self.buffer.resize(buf.len(), 0);
+            match self.original.read_exact(&mut self.buffer) {
+                Ok(()) => self.differs = self.buffer != buf,
+                Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => self.differs = true,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
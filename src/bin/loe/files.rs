@@ -0,0 +1,251 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::glob::Glob;
+use crate::ignore::{self, IgnoreFile};
+
+/// Input file given on the command line or found during walking a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputFile {
+    /// Path of the file.
+    pub path: PathBuf,
+    /// Path of the file relative to the parent of its command line argument. It is used for
+    /// mirroring the structure in the output directory.
+    pub relative: PathBuf,
+}
+
+/// Selection of files found during walking a directory. Files given directly on the command line
+/// are always selected.
+#[derive(Default)]
+pub struct Filter {
+    /// If not empty, only files matching at least one of the patterns are selected.
+    pub include: Vec<Glob>,
+    /// Files and directories matching any of the patterns are skipped.
+    pub exclude: Vec<Glob>,
+    /// Whether the rules in `.gitignore` and `.ignore` files are respected.
+    pub respect_ignore: bool,
+}
+
+impl Filter {
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.exclude.iter().any(|glob| glob.is_match(path))
+    }
+
+    fn is_included(&self, path: &Path) -> bool {
+        self.include.is_empty() || self.include.iter().any(|glob| glob.is_match(path))
+    }
+}
+
+/// State of walking a directory given on the command line.
+struct Walk<'a> {
+    root: &'a Path,
+    filter: &'a Filter,
+    ignore_files: Vec<IgnoreFile>,
+}
+
+/// Collects all files from given paths. Directories are walked only if `recursive` is true,
+/// otherwise they are reported as an error. Files in a directory are sorted by name so the order
+/// is deterministic. Symbolic links and special files found during walking are skipped. The
+/// patterns in the filter are matched against paths relative to the walked directory.
+pub fn collect<P: AsRef<Path>>(
+    paths: &[P],
+    recursive: bool,
+    filter: &Filter,
+) -> io::Result<Vec<InputFile>> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        let path = path.as_ref();
+        let relative = path.file_name().map(PathBuf::from).unwrap_or_default();
+
+        if fs::metadata(path)?.is_dir() {
+            if !recursive {
+                return Err(io::Error::other(format!(
+                    "'{}' is a directory (use --recursive)",
+                    path.display()
+                )));
+            }
+
+            let mut walk = Walk {
+                root: path,
+                filter,
+                ignore_files: Vec::new(),
+            };
+            walk.walk(path, &relative, &mut files)?;
+        } else {
+            files.push(InputFile {
+                path: path.to_path_buf(),
+                relative,
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+impl<'a> Walk<'a> {
+    fn walk(&mut self, dir: &Path, relative: &Path, files: &mut Vec<InputFile>) -> io::Result<()> {
+        let mut loaded = false;
+        if self.filter.respect_ignore {
+            let dir_relative = dir.strip_prefix(self.root).unwrap_or(dir);
+            if let Some(ignore_file) = IgnoreFile::load(dir, dir_relative)? {
+                self.ignore_files.push(ignore_file);
+                loaded = true;
+            }
+        }
+
+        let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+            let relative = relative.join(entry.file_name());
+            let root_relative = path.strip_prefix(self.root).unwrap_or(&path);
+
+            if self.filter.is_excluded(root_relative) {
+                continue;
+            }
+
+            if file_type.is_dir() {
+                if self.filter.respect_ignore
+                    && (entry.file_name() == ".git"
+                        || ignore::is_ignored(&self.ignore_files, root_relative, true))
+                {
+                    continue;
+                }
+
+                self.walk(&path, &relative, files)?;
+            } else if file_type.is_file() {
+                if !self.filter.is_included(root_relative)
+                    || (self.filter.respect_ignore
+                        && ignore::is_ignored(&self.ignore_files, root_relative, false))
+                {
+                    continue;
+                }
+
+                files.push(InputFile { path, relative });
+            }
+        }
+
+        if loaded {
+            self.ignore_files.pop();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn create_tree(root: &Path, files: &[&str]) {
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(path).unwrap();
+        }
+    }
+
+    fn relative(files: &[InputFile]) -> Vec<PathBuf> {
+        files.iter().map(|file| file.relative.clone()).collect()
+    }
+
+    #[test]
+    fn recursive() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("root");
+        create_tree(&root, &["b.txt", "a/c.txt", "a/b/d.txt"]);
+
+        let files = collect(&[&root], true, &Filter::default()).unwrap();
+        assert_eq!(
+            relative(&files),
+            vec![
+                PathBuf::from("root/a/b/d.txt"),
+                PathBuf::from("root/a/c.txt"),
+                PathBuf::from("root/b.txt"),
+            ]
+        );
+        assert_eq!(files[1].path, root.join("a/c.txt"));
+    }
+
+    #[test]
+    fn not_recursive() {
+        let tmp = tempfile::tempdir().unwrap();
+        create_tree(tmp.path(), &["a.txt", "dir/b.txt"]);
+
+        let files = collect(&[tmp.path().join("a.txt")], false, &Filter::default()).unwrap();
+        assert_eq!(relative(&files), vec![PathBuf::from("a.txt")]);
+
+        assert!(collect(&[tmp.path().join("dir")], false, &Filter::default()).is_err());
+    }
+
+    #[test]
+    fn include_exclude() {
+        let tmp = tempfile::tempdir().unwrap();
+        create_tree(
+            tmp.path(),
+            &["main.rs", "README.md", "src/lib.rs", "vendor/dep/lib.rs"],
+        );
+
+        let filter = Filter {
+            include: vec![Glob::new("*.rs")],
+            exclude: vec![Glob::new("vendor/**")],
+            respect_ignore: false,
+        };
+
+        let files = collect(&[tmp.path()], true, &filter).unwrap();
+        let name = PathBuf::from(tmp.path().file_name().unwrap());
+        assert_eq!(
+            relative(&files),
+            vec![name.join("main.rs"), name.join("src/lib.rs")]
+        );
+
+        // files given explicitly are not filtered
+        let files = collect(&[tmp.path().join("README.md")], true, &filter).unwrap();
+        assert_eq!(relative(&files), vec![PathBuf::from("README.md")]);
+    }
+
+    #[test]
+    fn respect_ignore() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("root");
+        create_tree(
+            &root,
+            &[
+                "main.rs",
+                "debug.log",
+                ".git/config",
+                "target/out.rs",
+                "sub/notes.txt",
+                "sub/keep.log",
+                "sub/local.rs",
+            ],
+        );
+        fs::write(root.join(".gitignore"), "*.log\ntarget/\n").unwrap();
+        fs::write(root.join("sub/.ignore"), "!keep.log\nlocal.rs\n").unwrap();
+
+        let filter = Filter {
+            respect_ignore: true,
+            ..Filter::default()
+        };
+
+        let files = collect(&[&root], true, &filter).unwrap();
+        assert_eq!(
+            relative(&files),
+            vec![
+                PathBuf::from("root/.gitignore"),
+                PathBuf::from("root/main.rs"),
+                PathBuf::from("root/sub/.ignore"),
+                PathBuf::from("root/sub/keep.log"),
+                PathBuf::from("root/sub/notes.txt"),
+            ]
+        );
+
+        let files = collect(&[&root], true, &Filter::default()).unwrap();
+        assert_eq!(files.len(), 9);
+    }
+}
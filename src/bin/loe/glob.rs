@@ -0,0 +1,184 @@
+use std::path::Path;
+
+/// Shell-like glob pattern used for selecting files. Supported syntax is `*` (anything except
+/// `/`), `?` (single character except `/`), `**` (any number of directories), character classes
+/// like `[a-z]` or `[!0-9]` and `\` for escaping.
+///
+/// Patterns without a slash are matched against the file name only, so `*.rs` selects Rust files
+/// in all directories. Other patterns are matched against the whole relative path. A leading
+/// slash is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: Vec<char>,
+    basename: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        let basename = !pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+        Glob {
+            pattern: pattern.chars().collect(),
+            basename,
+        }
+    }
+
+    /// Returns true if the relative path matches the pattern.
+    pub fn is_match(&self, path: &Path) -> bool {
+        let path = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let text = if self.basename {
+            path.rsplit('/').next().unwrap_or_default()
+        } else {
+            &path
+        };
+
+        matches(&self.pattern, &text.chars().collect::<Vec<_>>())
+    }
+}
+
+fn matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+
+            if let Some(rest) = rest.strip_prefix(&['/']) {
+                // zero or more whole directories
+                matches(rest, text)
+                    || (0..text.len()).any(|i| text[i] == '/' && matches(rest, &text[i + 1..]))
+            } else {
+                (0..=text.len()).any(|i| matches(rest, &text[i..]))
+            }
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+
+            for i in 0..=text.len() {
+                if matches(rest, &text[i..]) {
+                    return true;
+                }
+
+                if i < text.len() && text[i] == '/' {
+                    break;
+                }
+            }
+
+            false
+        }
+        Some('?') => match text.first() {
+            Some(c) if *c != '/' => matches(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+        Some('[') => match (parse_class(&pattern[1..]), text.first()) {
+            (Some((class, rest)), Some(c)) => {
+                *c != '/' && class.contains(*c) && matches(rest, &text[1..])
+            }
+            (Some(_), None) => false,
+            (None, _) => text.first() == Some(&'[') && matches(&pattern[1..], &text[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && matches(&pattern[2..], &text[1..])
+        }
+        Some(c) => text.first() == Some(c) && matches(&pattern[1..], &text[1..]),
+    }
+}
+
+struct Class {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl Class {
+    fn contains(&self, c: char) -> bool {
+        self.ranges
+            .iter()
+            .any(|(start, end)| *start <= c && c <= *end)
+            != self.negated
+    }
+}
+
+/// Parses a character class which starts after the opening bracket. Returns the class and the
+/// rest of the pattern, or `None` if the class is not closed.
+fn parse_class(pattern: &[char]) -> Option<(Class, &[char])> {
+    let (negated, mut i) = match pattern.first() {
+        Some('!') | Some('^') => (true, 1),
+        _ => (false, 0),
+    };
+
+    let mut ranges = Vec::new();
+    let mut first = true;
+
+    while i < pattern.len() {
+        let c = pattern[i];
+
+        if c == ']' && !first {
+            return Some((Class { negated, ranges }, &pattern[i + 1..]));
+        }
+
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|c| *c != ']') {
+            ranges.push((c, pattern[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+
+        first = false;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).is_match(Path::new(path))
+    }
+
+    #[test]
+    fn basename() {
+        assert!(is_match("*.rs", "main.rs"));
+        assert!(is_match("*.rs", "src/bin/main.rs"));
+        assert!(!is_match("*.rs", "main.rs.bak"));
+        assert!(is_match("Cargo.to?l", "sub/Cargo.toml"));
+        assert!(is_match("*", "a/b/c"));
+    }
+
+    #[test]
+    fn path() {
+        assert!(is_match("src/*.rs", "src/lib.rs"));
+        assert!(!is_match("src/*.rs", "src/bin/main.rs"));
+        assert!(is_match("/src/*.rs", "src/lib.rs"));
+        assert!(!is_match("src/*.rs", "sub/src/lib.rs"));
+    }
+
+    #[test]
+    fn double_star() {
+        assert!(is_match("vendor/**", "vendor/a.rs"));
+        assert!(is_match("vendor/**", "vendor/a/b/c.rs"));
+        assert!(!is_match("vendor/**", "src/vendor/a.rs"));
+        assert!(is_match("**/vendor/*.rs", "vendor/a.rs"));
+        assert!(is_match("**/vendor/*.rs", "a/b/vendor/a.rs"));
+        assert!(is_match("src/**/*.rs", "src/lib.rs"));
+        assert!(is_match("src/**/*.rs", "src/bin/loe/main.rs"));
+        assert!(!is_match("src/**/*.rs", "tests/main.rs"));
+    }
+
+    #[test]
+    fn class() {
+        assert!(is_match("file[0-9].txt", "file1.txt"));
+        assert!(!is_match("file[0-9].txt", "filea.txt"));
+        assert!(is_match("file[!0-9].txt", "filea.txt"));
+        assert!(is_match("[]a].txt", "].txt"));
+        assert!(is_match("a[b", "a[b"));
+        assert!(is_match("\\*.txt", "*.txt"));
+        assert!(!is_match("\\*.txt", "a.txt"));
+    }
+}
@@ -0,0 +1,142 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::glob::Glob;
+
+/// Names of files with ignore rules, in the order of increasing precedence.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+struct Rule {
+    glob: Glob,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// Ignore rules loaded from `.gitignore` and `.ignore` files in a directory. The syntax follows
+/// gitignore: comments, negation using `!`, trailing slash for directories only and anchoring of
+/// patterns containing a slash.
+pub struct IgnoreFile {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl IgnoreFile {
+    /// Loads the rules from `dir` which is located at `relative` path within the walked root.
+    /// Returns `None` if there are no ignore files in the directory.
+    pub fn load(dir: &Path, relative: &Path) -> io::Result<Option<IgnoreFile>> {
+        let mut rules = Vec::new();
+        let mut found = false;
+
+        for name in IGNORE_FILES {
+            match fs::read_to_string(dir.join(name)) {
+                Ok(content) => {
+                    rules.extend(parse(&content));
+                    found = true;
+                }
+                Err(ref err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        if found {
+            Ok(Some(IgnoreFile {
+                dir: relative.to_path_buf(),
+                rules,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns `Some(true)` if the path is ignored by the rules, `Some(false)` if it is explicitly
+    /// not ignored by a negated rule and `None` if no rule applies.
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let path = path.strip_prefix(&self.dir).ok()?;
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.is_match(path))
+            .map(|rule| !rule.negated)
+    }
+}
+
+fn parse(content: &str) -> Vec<Rule> {
+    content
+        .lines()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(line) => (true, line),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+
+            Rule {
+                glob: Glob::new(line),
+                negated,
+                dir_only,
+            }
+        })
+        .collect()
+}
+
+/// Returns true if the path relative to the walked root is ignored by given ignore files. The
+/// files must be ordered from the outermost directory, so rules in nested directories take
+/// precedence.
+pub fn is_ignored(files: &[IgnoreFile], path: &Path, is_dir: bool) -> bool {
+    files
+        .iter()
+        .rev()
+        .find_map(|file| file.matched(path, is_dir))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignore_file(dir: &str, content: &str) -> IgnoreFile {
+        IgnoreFile {
+            dir: PathBuf::from(dir),
+            rules: parse(content),
+        }
+    }
+
+    #[test]
+    fn rules() {
+        let files = [ignore_file(
+            "",
+            "# comment\n*.log\n!keep.log\ntarget/\n/build\n",
+        )];
+
+        assert!(is_ignored(&files, Path::new("a.log"), false));
+        assert!(is_ignored(&files, Path::new("sub/a.log"), false));
+        assert!(!is_ignored(&files, Path::new("keep.log"), false));
+        assert!(is_ignored(&files, Path::new("sub/target"), true));
+        assert!(!is_ignored(&files, Path::new("sub/target"), false));
+        assert!(is_ignored(&files, Path::new("build"), true));
+        assert!(!is_ignored(&files, Path::new("sub/build"), true));
+        assert!(!is_ignored(&files, Path::new("main.rs"), false));
+    }
+
+    #[test]
+    fn nested() {
+        let files = [
+            ignore_file("", "*.txt\n"),
+            ignore_file("sub", "!notes.txt\n/local.rs\n"),
+        ];
+
+        assert!(is_ignored(&files, Path::new("notes.txt"), false));
+        assert!(!is_ignored(&files, Path::new("sub/notes.txt"), false));
+        assert!(is_ignored(&files, Path::new("sub/other.txt"), false));
+        assert!(is_ignored(&files, Path::new("sub/local.rs"), false));
+        assert!(!is_ignored(&files, Path::new("local.rs"), false));
+    }
+}
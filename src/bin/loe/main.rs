@@ -0,0 +1,684 @@
+extern crate clap;
+extern crate loe;
+
+mod compare;
+mod files;
+mod glob;
+mod ignore;
+mod mmap;
+mod pool;
+mod replace;
+
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{App, Arg};
+use loe::{
+    is_binary, process, process_slice_parallel, Bom, Config, Encoding, ParseError, Report,
+    TransformMode, BINARY_CHECK_SIZE, DEFAULT_BUFFER_SIZE,
+};
+use yansi::Paint;
+
+use crate::compare::Compare;
+use crate::files::{Filter, InputFile};
+use crate::glob::Glob;
+use crate::mmap::Mmap;
+
+fn print_error_and_exit<T: fmt::Display>(message: T) -> ! {
+    eprintln!("{} {}", Paint::red("error:"), message);
+    process::exit(1);
+}
+
+/// Parses the size in bytes with optional `K` or `M` suffix (powers of 1024).
+fn parse_size(value: &str) -> Option<usize> {
+    let (number, multiplier) = match value.chars().last()? {
+        'k' | 'K' => (&value[..value.len() - 1], 1024),
+        'm' | 'M' => (&value[..value.len() - 1], 1024 * 1024),
+        _ => (value, 1),
+    };
+
+    number
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .filter(|size| *size > 0)
+}
+
+/// Environment variable which overrides the native line ending.
+const NATIVE_EOL_VAR: &str = "LOE_NATIVE_EOL";
+
+/// Parses the name of a line ending as accepted by `--ending`.
+fn parse_ending(value: &str) -> Option<TransformMode> {
+    match value {
+        "lf" => Some(TransformMode::Lf),
+        "crlf" => Some(TransformMode::Crlf),
+        "cr" => Some(TransformMode::Cr),
+        "auto" => Some(TransformMode::Auto),
+        "native" => Some(TransformMode::Native),
+        _ => None,
+    }
+}
+
+/// Parses the line ending which overrides the native one. Only concrete line endings are allowed.
+fn parse_native_ending(value: &str) -> Option<TransformMode> {
+    parse_ending(value).filter(|mode| {
+        matches!(
+            mode,
+            TransformMode::Lf | TransformMode::Crlf | TransformMode::Cr
+        )
+    })
+}
+
+/// Returns the line ending which overrides the native one if the environment variable is set.
+/// Exits if its value is not valid.
+fn native_override() -> Option<TransformMode> {
+    match env::var(NATIVE_EOL_VAR) {
+        Ok(value) => match parse_native_ending(&value) {
+            Some(mode) => Some(mode),
+            None => print_error_and_exit(format!(
+                "invalid value of {}: {} (expected lf, crlf or cr)",
+                NATIVE_EOL_VAR, value
+            )),
+        },
+        Err(env::VarError::NotPresent) => None,
+        Err(env::VarError::NotUnicode(value)) => print_error_and_exit(format!(
+            "invalid value of {}: {:?} (expected lf, crlf or cr)",
+            NATIVE_EOL_VAR, value
+        )),
+    }
+}
+
+/// Where the transformed files are written to.
+enum Destination {
+    /// Next to the input file with `.out` extension appended (only for single input file).
+    Default,
+    /// Given filepath (only for single input file).
+    File(PathBuf),
+    /// The input file is replaced.
+    InPlace,
+    /// Given directory which mirrors the structure of the input.
+    Directory(PathBuf),
+}
+
+impl Destination {
+    /// Returns the output path for given input file, or `None` if the file is converted in place.
+    fn output_path(&self, file: &InputFile) -> Option<PathBuf> {
+        let output = match self {
+            Destination::Default => {
+                let mut output = file.path.clone().into_os_string();
+                output.push(".out");
+                PathBuf::from(output)
+            }
+            Destination::File(path) => path.clone(),
+            Destination::InPlace => return None,
+            Destination::Directory(dir) => dir.join(&file.relative),
+        };
+
+        if output == file.path {
+            None
+        } else {
+            Some(output)
+        }
+    }
+}
+
+/// Result of handling a single file.
+enum Outcome {
+    /// The file was converted and written to given path (or in place if there is none).
+    Converted(Option<PathBuf>, Report),
+    /// The file was to be converted in place, but it already is in the requested form, so it was
+    /// left untouched.
+    Unchanged(Report),
+    /// The file conforms to the requested line ending (and encoding).
+    Conforming(Report),
+    /// The file does not conform to the requested line ending (and encoding).
+    NonConforming(Report),
+    /// The file was skipped because it looks like binary.
+    Binary,
+}
+
+/// Describes the report of a file for the verbose output. In check mode, nothing was converted, so
+/// the line endings are described as the ones to convert.
+fn describe(report: &Report, check: bool) -> String {
+    format!(
+        "{} lines, {} line endings {} (CRLF: {}, LF: {}, CR: {}), {} bytes read, {} bytes {}{}",
+        report.lines(),
+        report.converted(),
+        if check { "to convert" } else { "converted" },
+        report.converted_crlf(),
+        report.converted_lf(),
+        report.converted_cr(),
+        report.bytes_read(),
+        report.bytes_written(),
+        if check { "in output" } else { "written" },
+        if report.is_changed() {
+            ""
+        } else {
+            ", unchanged"
+        }
+    )
+}
+
+/// Reads the beginning of the file which is used for detecting binary data and byte order mark.
+fn read_head(path: &Path) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(BINARY_CHECK_SIZE);
+    File::open(path)?
+        .take(BINARY_CHECK_SIZE as u64)
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// How the input files are processed.
+struct Options {
+    /// Whether the input files are memory-mapped.
+    mmap: bool,
+    /// Number of threads used for converting a single file.
+    jobs: usize,
+    /// Whether the output files get the modification time of the input files.
+    keep_mtime: bool,
+}
+
+/// Processes the file and writes the result into the output. If requested and possible, the file
+/// is memory-mapped instead of being read.
+fn process_file<O: Write>(
+    path: &Path,
+    output: &mut O,
+    config: Config<Encoding, TransformMode>,
+    options: &Options,
+) -> Result<Report, ParseError> {
+    let mut input = File::open(path).map_err(ParseError::IoError)?;
+
+    // parallel processing needs the whole input in memory
+    if options.mmap || options.jobs > 1 {
+        if let Some(mmap) = Mmap::map(&input).map_err(ParseError::IoError)? {
+            return process_slice_parallel(&mmap, output, config, options.jobs);
+        }
+    }
+
+    process(&mut input, output, config)
+}
+
+/// Determines the line ending which dominates in the file by processing it without writing the
+/// output. LF is used if there are no line endings.
+fn dominant_ending(
+    path: &Path,
+    config: Config<Encoding, TransformMode>,
+    options: &Options,
+) -> Result<TransformMode, ParseError> {
+    let config = config.transform(TransformMode::Lf);
+    let report = process_file(path, &mut io::sink(), config, options)?;
+    Ok(report
+        .line_endings()
+        .dominant()
+        .unwrap_or(TransformMode::Lf))
+}
+
+/// Checks if the file conforms to the config, that is, if processing would not change it. The
+/// report of the processing is returned too.
+fn check_file(
+    path: &Path,
+    config: Config<Encoding, TransformMode>,
+    options: &Options,
+) -> Result<(bool, Report), ParseError> {
+    let original = File::open(path).map_err(ParseError::IoError)?;
+    let mut compare = Compare::new(original);
+
+    let report = process_file(path, &mut compare, config, options)?;
+    let differs = compare.differs().map_err(ParseError::IoError)?;
+    Ok((!differs, report))
+}
+
+/// Converts the file and writes the result into given output path. If no path is given, the file
+/// is replaced with the result unless it would not change.
+fn convert_file(
+    path: &Path,
+    output_path: Option<&Path>,
+    config: Config<Encoding, TransformMode>,
+    options: &Options,
+) -> Result<Report, ParseError> {
+    let output_path = match output_path {
+        Some(output_path) => output_path,
+        None => {
+            return replace::replace_file(
+                path,
+                options.keep_mtime,
+                |output| process_file(path, output, config, options),
+                Report::is_changed,
+            );
+        }
+    };
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).map_err(ParseError::IoError)?;
+    }
+
+    let mut output = File::create(output_path).map_err(ParseError::IoError)?;
+    let report = process_file(path, &mut output, config, options)?;
+
+    if options.keep_mtime {
+        let metadata = fs::metadata(path).map_err(ParseError::IoError)?;
+        replace::file_times(&metadata)
+            .and_then(|times| output.set_times(times))
+            .map_err(ParseError::IoError)?;
+    }
+
+    Ok(report)
+}
+
+fn main() {
+    let matches = App::new("loe")
+        .version("0.3.0")
+        .about("Changes line endings to LF or CRLF")
+        .author("Petr Nevyhoštěný")
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .help("Specifies a filepath where the transformed file is written to. If it is identical to the input filepath, the content is safely replaced (no data loss). Allowed only for a single input file.")
+                .takes_value(true)
+                .conflicts_with_all(&["in-place", "output-dir"]),
+        ).arg(
+            Arg::with_name("in-place")
+                .short("i")
+                .long("in-place")
+                .help("Safely replaces the content of all input files (no data loss). The files are replaced atomically and their permissions, ownership and extended attributes are preserved. Files which are already in the requested form are left untouched.")
+                .conflicts_with("output-dir"),
+        ).arg(
+            Arg::with_name("output-dir")
+                .short("d")
+                .long("output-dir")
+                .value_name("DIR")
+                .help("Specifies a directory where the transformed files are written to. The structure of input directories is mirrored.")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("keep-date")
+                .short("k")
+                .long("keep-date")
+                .help("Keeps the modification time of the input files in the output files."),
+        ).arg(
+            Arg::with_name("recursive")
+                .short("r")
+                .long("recursive")
+                .help("Walks input directories and processes all files in them."),
+        ).arg(
+            Arg::with_name("include")
+                .long("include")
+                .value_name("GLOB")
+                .help("Processes only files matching the pattern when walking directories. Patterns without a slash are matched against file names, others against paths relative to the walked directory. Can be used multiple times.")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        ).arg(
+            Arg::with_name("exclude")
+                .long("exclude")
+                .value_name("GLOB")
+                .help("Skips files and directories matching the pattern when walking directories. Can be used multiple times.")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        ).arg(
+            Arg::with_name("respect-ignore")
+                .long("respect-ignore")
+                .help("Skips files ignored by .gitignore and .ignore files (and .git directories) when walking directories."),
+        ).arg(
+            Arg::with_name("FILE")
+                .help("Input files (or directories with --recursive).")
+                .required(true)
+                .multiple(true),
+        ).arg(
+            Arg::with_name("encoding")
+                .short("e")
+                .long("encoding")
+                .help("Enables checking of encoding in the input file. By default, no checks are performed (but UTF-16 input with byte order mark is still recognized). Line endings in UTF-16 input are converted as 16-bit code units.")
+                .takes_value(true)
+                .possible_values(&["utf8", "ascii", "utf16", "utf16le", "utf16be", "latin1", "windows1252", "cp437"])
+                .value_name("utf8|ascii|utf16|utf16le|utf16be|latin1|windows1252|cp437"),
+        ).arg(
+            Arg::with_name("to-utf8")
+                .long("to-utf8")
+                .help("Converts the input into UTF-8. It has effect only with single-byte encodings (latin1, windows1252 and cp437).")
+                .requires("encoding"),
+        ).arg(
+            Arg::with_name("ending")
+                .short("n")
+                .long("ending")
+                .help("Specifies what line ending sequence is used. With auto, the line ending which dominates in each file is used and the others are converted to it. Native is CRLF on Windows and LF elsewhere, it can be overridden by LOE_NATIVE_EOL environment variable (lf, crlf or cr).")
+                .takes_value(true)
+                .possible_values(&["lf", "crlf", "cr", "auto", "native"])
+                .value_name("lf|crlf|cr|auto|native")
+                .default_value("lf"),
+        ).arg(
+            Arg::with_name("keep-bom")
+                .long("keep-bom")
+                .help("Keeps the byte order mark if present (default)."),
+        ).arg(
+            Arg::with_name("remove-bom")
+                .long("remove-bom")
+                .help("Removes the byte order mark if present.")
+                .conflicts_with("keep-bom"),
+        ).arg(
+            Arg::with_name("add-bom")
+                .long("add-bom")
+                .help("Adds the byte order mark if missing. It is UTF-8 one unless the encoding is UTF-16.")
+                .conflicts_with_all(&["keep-bom", "remove-bom"]),
+        ).arg(
+            Arg::with_name("check")
+                .short("c")
+                .long("check")
+                .help("Only checks if the input files conform to the requested line ending (and encoding). Nothing is written and the program exits with non-zero code if any file would be changed.")
+                .conflicts_with_all(&["output", "in-place", "output-dir"]),
+        ).arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("Prints details about every processed file: the number of lines, converted line endings and bytes read and written."),
+        ).arg(
+            Arg::with_name("force")
+                .short("f")
+                .long("force")
+                .help("Processes also files which look like binary. By default, they are skipped."),
+        ).arg(
+            Arg::with_name("mmap")
+                .long("mmap")
+                .help("Memory-maps regular input files instead of reading them, which is faster for large files. The files must not be modified by other programs during the processing.")
+        ).arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .help("Number of threads. Multiple input files are processed concurrently, the results are still reported in order. A single large regular file is split into chunks which are converted in parallel (the file is memory-mapped), UTF-16 files are always converted on a single thread.")
+                .takes_value(true)
+                .value_name("N")
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(jobs) if jobs > 0 => Ok(()),
+                    _ => Err(format!("invalid number of jobs: {}", value)),
+                }),
+        ).arg(
+            Arg::with_name("buffer-size")
+                .long("buffer-size")
+                .help("Size of the buffer used for reading the input. The suffixes K and M for KiB and MiB are accepted. Default is 64K.")
+                .takes_value(true)
+                .value_name("SIZE")
+                .validator(|value| {
+                    parse_size(&value)
+                        .map(|_| ())
+                        .ok_or_else(|| format!("invalid buffer size: {}", value))
+                }),
+        ).get_matches();
+
+    let input_paths = matches.values_of("FILE").unwrap().collect::<Vec<_>>();
+    let globs = |name| {
+        matches
+            .values_of(name)
+            .map(|values| values.map(Glob::new).collect())
+            .unwrap_or_default()
+    };
+    let filter = Filter {
+        include: globs("include"),
+        exclude: globs("exclude"),
+        respect_ignore: matches.is_present("respect-ignore"),
+    };
+    let files = files::collect(&input_paths, matches.is_present("recursive"), &filter)
+        .unwrap_or_else(|err| print_error_and_exit(err));
+
+    let destination = if let Some(output) = matches.value_of("output") {
+        if files.len() != 1 || matches.is_present("recursive") {
+            print_error_and_exit("--output can be used only for a single input file");
+        }
+        Destination::File(PathBuf::from(output))
+    } else if matches.is_present("in-place") {
+        Destination::InPlace
+    } else if let Some(dir) = matches.value_of("output-dir") {
+        Destination::Directory(PathBuf::from(dir))
+    } else if (input_paths.len() > 1 || matches.is_present("recursive"))
+        && !matches.is_present("check")
+    {
+        // the outputs would be scattered next to the inputs and walked by the next run
+        print_error_and_exit(
+            "--in-place or --output-dir is required for multiple input files and --recursive",
+        );
+    } else {
+        Destination::Default
+    };
+
+    let encoding = matches
+        .value_of("encoding")
+        .map(|e| match e {
+            "utf8" => Encoding::Utf8,
+            "ascii" => Encoding::Ascii,
+            "utf16" => Encoding::Utf16,
+            "utf16le" => Encoding::Utf16Le,
+            "utf16be" => Encoding::Utf16Be,
+            "latin1" => Encoding::Latin1,
+            "windows1252" => Encoding::Windows1252,
+            "cp437" => Encoding::Cp437,
+            _ => unreachable!(),
+        })
+        .unwrap_or(Encoding::Ignore);
+
+    let transform = matches
+        .value_of("ending")
+        .and_then(parse_ending)
+        .map(|mode| match mode {
+            TransformMode::Native => TransformMode::native_or(native_override()),
+            mode => mode,
+        })
+        .unwrap();
+
+    let bom = if matches.is_present("remove-bom") {
+        Bom::Remove
+    } else if matches.is_present("add-bom") {
+        Bom::Add
+    } else {
+        Bom::Keep
+    };
+
+    let config = Config::default()
+        .encoding(encoding)
+        .transform(transform)
+        .bom(bom)
+        .transcode(matches.is_present("to-utf8"))
+        .buffer_size(
+            matches
+                .value_of("buffer-size")
+                .and_then(parse_size)
+                .unwrap_or(DEFAULT_BUFFER_SIZE),
+        );
+    let check = matches.is_present("check");
+    let verbose = matches.is_present("verbose");
+
+    let force = matches.is_present("force");
+    let mut options = Options {
+        mmap: matches.is_present("mmap"),
+        jobs: matches
+            .value_of("jobs")
+            .map(|jobs| jobs.parse().unwrap())
+            .unwrap_or(1),
+        keep_mtime: matches.is_present("keep-date"),
+    };
+    // with multiple files, the files are processed concurrently and each of them on one thread
+    let workers = if files.len() > 1 {
+        mem::replace(&mut options.jobs, 1)
+    } else {
+        1
+    };
+
+    let utf16 = matches!(
+        encoding,
+        Encoding::Utf16 | Encoding::Utf16Le | Encoding::Utf16Be
+    );
+
+    let handle = |file: &InputFile| -> Result<Outcome, ParseError> {
+        let head = read_head(&file.path).map_err(ParseError::IoError)?;
+
+        // UTF-16 text looks like binary because of NUL bytes
+        if !force && !utf16 && is_binary(&head) {
+            return Ok(Outcome::Binary);
+        }
+
+        let config = if encoding == Encoding::Ignore
+            && (head.starts_with(&[0xff, 0xfe]) || head.starts_with(&[0xfe, 0xff]))
+        {
+            // UTF-16 with byte order mark would be corrupted by processing it as bytes
+            config.clone().encoding(Encoding::Utf16)
+        } else {
+            config.clone()
+        };
+
+        // the whole file is scanned first instead of looking ahead in memory
+        let config = if transform == TransformMode::Auto {
+            let ending = dominant_ending(&file.path, config.clone(), &options)?;
+            config.transform(ending)
+        } else {
+            config
+        };
+
+        if check {
+            match check_file(&file.path, config, &options)? {
+                (true, report) => Ok(Outcome::Conforming(report)),
+                (false, report) => Ok(Outcome::NonConforming(report)),
+            }
+        } else {
+            let output_path = destination.output_path(file);
+            let report = convert_file(&file.path, output_path.as_deref(), config, &options)?;
+            if output_path.is_none() && !report.is_changed() {
+                Ok(Outcome::Unchanged(report))
+            } else {
+                Ok(Outcome::Converted(output_path, report))
+            }
+        }
+    };
+
+    let mut succeeded = 0;
+    let mut unchanged = 0;
+    let mut skipped = 0;
+    let mut failed = 0;
+
+    pool::run_ordered(&files, workers, handle, |file, result| {
+        let path = file.path.display();
+
+        let report = match result {
+            Ok(Outcome::Converted(Some(output_path), report)) => {
+                println!(
+                    "{} {} -> {}",
+                    Paint::green("converted:"),
+                    path,
+                    output_path.display()
+                );
+                succeeded += 1;
+                Some(report)
+            }
+            Ok(Outcome::Converted(None, report)) => {
+                println!("{} {}", Paint::green("converted:"), path);
+                succeeded += 1;
+                Some(report)
+            }
+            Ok(Outcome::Unchanged(report)) => {
+                println!("{} {}", Paint::green("unchanged:"), path);
+                unchanged += 1;
+                Some(report)
+            }
+            Ok(Outcome::Conforming(report)) => {
+                if verbose {
+                    println!("{} {}", Paint::green("conforming:"), path);
+                }
+                succeeded += 1;
+                Some(report)
+            }
+            Ok(Outcome::NonConforming(report)) => {
+                println!("{} {}", Paint::yellow("non-conforming:"), path);
+                failed += 1;
+                Some(report)
+            }
+            Ok(Outcome::Binary) => {
+                println!("{} {} (binary)", Paint::yellow("skipped:"), path);
+                skipped += 1;
+                None
+            }
+            Err(err @ ParseError::InvalidEncoding { .. })
+            | Err(err @ ParseError::IncompleteEncoding { .. }) => {
+                // the error starts with line and column
+                eprintln!("{} {}:{}", Paint::red("error:"), path, err);
+                failed += 1;
+                None
+            }
+            Err(err) => {
+                eprintln!("{} {}: {}", Paint::red("error:"), path, err);
+                failed += 1;
+                None
+            }
+        };
+
+        if let Some(report) = report.filter(|_| verbose) {
+            println!("    {}", describe(&report, check));
+        }
+    });
+
+    if files.len() > 1 {
+        if check {
+            println!(
+                "{} files checked, {} conforming, {} skipped, {} non-conforming or failed",
+                files.len(),
+                succeeded,
+                skipped,
+                failed
+            );
+        } else {
+            println!(
+                "{} files processed, {} converted, {} unchanged, {} skipped, {} failed",
+                files.len(),
+                succeeded,
+                unchanged,
+                skipped,
+                failed
+            );
+        }
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size() {
+        assert_eq!(parse_size("1"), Some(1));
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("64K"), Some(64 * 1024));
+        assert_eq!(parse_size("2m"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_size("0"), None);
+        assert_eq!(parse_size("K"), None);
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("1G"), None);
+    }
+
+    #[test]
+    fn ending() {
+        assert_eq!(parse_ending("lf"), Some(TransformMode::Lf));
+        assert_eq!(parse_ending("crlf"), Some(TransformMode::Crlf));
+        assert_eq!(parse_ending("cr"), Some(TransformMode::Cr));
+        assert_eq!(parse_ending("auto"), Some(TransformMode::Auto));
+        assert_eq!(parse_ending("native"), Some(TransformMode::Native));
+        assert_eq!(parse_ending("CRLF"), None);
+        assert_eq!(parse_ending(""), None);
+    }
+
+    #[test]
+    fn native_ending() {
+        assert_eq!(parse_native_ending("lf"), Some(TransformMode::Lf));
+        assert_eq!(parse_native_ending("crlf"), Some(TransformMode::Crlf));
+        assert_eq!(parse_native_ending("cr"), Some(TransformMode::Cr));
+        assert_eq!(parse_native_ending("auto"), None);
+        assert_eq!(parse_native_ending("native"), None);
+        assert_eq!(parse_native_ending("windows"), None);
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+
+/// Read-only memory mapping of a whole file. The file must not be modified while it is mapped,
+/// otherwise the process may crash or see inconsistent data.
+pub struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mmap {
+    /// Maps the file into memory. Returns `None` if the file cannot be mapped because it is not a
+    /// regular file, it is empty or memory mapping is not supported on the platform.
+    #[cfg(unix)]
+    pub fn map(file: &File) -> io::Result<Option<Mmap>> {
+        use std::os::unix::io::AsRawFd;
+        use std::ptr;
+
+        let metadata = file.metadata()?;
+        if !metadata.is_file() || metadata.len() == 0 || metadata.len() > usize::MAX as u64 {
+            return Ok(None);
+        }
+
+        let len = metadata.len() as usize;
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Some(Mmap { ptr, len }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn map(_file: &File) -> io::Result<Option<Mmap>> {
+        Ok(None)
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // the mapping is valid until it is dropped
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    #[test]
+    fn map() {
+        let mut file = tempfile::tempfile().unwrap();
+        assert!(Mmap::map(&file).unwrap().is_none());
+
+        file.write_all(b"hello\r\nworld!\r\n").unwrap();
+        let mmap = Mmap::map(&file).unwrap().unwrap();
+        assert_eq!(&*mmap, b"hello\r\nworld!\r\n");
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Runs the job for every item on given number of worker threads. The results are passed to
+/// `report` on the current thread in the order of the items, as soon as all preceding results are
+/// available.
+pub fn run_ordered<T, R, J, F>(items: &[T], workers: usize, job: J, mut report: F)
+where
+    T: Sync,
+    R: Send,
+    J: Fn(&T) -> R + Sync,
+    F: FnMut(&T, R),
+{
+    if workers <= 1 {
+        for item in items {
+            report(item, job(item));
+        }
+        return;
+    }
+
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..workers.min(items.len()) {
+            let sender = sender.clone();
+            let next = &next;
+            let job = &job;
+
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= items.len() {
+                    break;
+                }
+
+                if sender.send((index, job(&items[index]))).is_err() {
+                    break;
+                }
+            });
+        }
+
+        // the loop below ends when all workers are finished
+        drop(sender);
+
+        let mut finished = BTreeMap::new();
+        let mut expected = 0;
+
+        for (index, result) in receiver {
+            finished.insert(index, result);
+
+            while let Some(result) = finished.remove(&expected) {
+                report(&items[expected], result);
+                expected += 1;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn ordered() {
+        let items = (0..50).collect::<Vec<u64>>();
+
+        for workers in 1..5 {
+            let mut results = Vec::new();
+            run_ordered(
+                &items,
+                workers,
+                |item| {
+                    // later items finish sooner
+                    thread::sleep(Duration::from_millis((50 - item) % 7));
+                    item * 2
+                },
+                |item, result| results.push((*item, result)),
+            );
+
+            let expected = items
+                .iter()
+                .map(|item| (*item, item * 2))
+                .collect::<Vec<_>>();
+            assert_eq!(results, expected);
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let mut called = false;
+        run_ordered(&[] as &[u8], 4, |_| (), |_, _| called = true);
+        assert!(!called);
+    }
+}
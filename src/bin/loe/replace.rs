@@ -0,0 +1,545 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File, FileTimes, Metadata, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use loe::ParseError;
+
+/// Counter which makes the names of temporary files unique.
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Temporary file which is removed when dropped unless it was persisted.
+struct TempFile {
+    path: PathBuf,
+    file: File,
+    persisted: bool,
+}
+
+impl TempFile {
+    /// Creates a new temporary file in given directory. The name is derived from the name of the
+    /// file which is going to be replaced.
+    fn create(dir: &Path, name: &OsString) -> io::Result<Self> {
+        // files with the same name can be converted concurrently
+        let id = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut tmp_name = OsString::from(".");
+        tmp_name.push(name);
+        tmp_name.push(format!(".loe-{}-{}.tmp", process::id(), id));
+
+        let path = dir.join(tmp_name);
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        restrict_permissions(&mut options);
+        let file = options.open(&path)?;
+
+        Ok(TempFile {
+            path,
+            file,
+            persisted: false,
+        })
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Replaces the content of the file with the output of `convert` and returns its result. The
+/// output is written into a temporary file next to the original, which then atomically replaces it
+/// by renaming. Permissions, ownership and extended attributes of the original are copied to the
+/// new file, and also the modification time if `keep_mtime` is true.
+///
+/// If `is_changed` returns false for the result, the output is discarded and the file is left
+/// untouched, so its modification time does not change.
+///
+/// If the temporary file cannot be created in the same directory, the metadata cannot be fully
+/// preserved or the file has multiple hard links, the output is written into the system temporary
+/// directory and then copied over the content of the original file. It is not atomic, but the file
+/// keeps its identity and so all of its metadata. If the copying fails, the temporary file is kept
+/// and the error names it.
+///
+/// Symbolic links are followed, so the file they point to is replaced.
+pub fn replace_file<F, P, R>(
+    path: &Path,
+    keep_mtime: bool,
+    convert: F,
+    is_changed: P,
+) -> Result<R, ParseError>
+where
+    F: FnOnce(&mut File) -> Result<R, ParseError>,
+    P: FnOnce(&R) -> bool,
+{
+    let path = fs::canonicalize(path).map_err(ParseError::IoError)?;
+    let metadata = fs::metadata(&path).map_err(ParseError::IoError)?;
+    let name = path.file_name().unwrap_or_default().to_os_string();
+
+    let next_to = match path.parent() {
+        Some(dir) if !has_hard_links(&metadata) => TempFile::create(dir, &name).ok(),
+        _ => None,
+    };
+
+    let mut tmp = match next_to {
+        Some(tmp) => tmp,
+        None => TempFile::create(&env::temp_dir(), &name).map_err(ParseError::IoError)?,
+    };
+
+    let result = convert(&mut tmp.file)?;
+    if !is_changed(&result) {
+        return Ok(result);
+    }
+
+    if tmp.path.parent() == path.parent()
+        && copy_metadata(&metadata, &path, &tmp.file, keep_mtime).is_ok()
+        && tmp.file.sync_all().is_ok()
+        && fs::rename(&tmp.path, &path).is_ok()
+    {
+        tmp.persisted = true;
+        return Ok(result);
+    }
+
+    let original = copy_back(&mut tmp, || {
+        OpenOptions::new().write(true).truncate(true).open(&path)
+    })?;
+
+    if keep_mtime {
+        file_times(&metadata)
+            .and_then(|times| original.set_times(times))
+            .map_err(ParseError::IoError)?;
+    }
+
+    Ok(result)
+}
+
+/// Copies the content of the temporary file into the output returned by `open`, which truncates
+/// the original file. Until the copying succeeds, the temporary file holds the only complete
+/// content, so it is removed only afterwards and any error names it.
+fn copy_back<W, F>(tmp: &mut TempFile, open: F) -> Result<W, ParseError>
+where
+    W: Write,
+    F: FnOnce() -> io::Result<W>,
+{
+    tmp.file
+        .seek(SeekFrom::Start(0))
+        .map_err(ParseError::IoError)?;
+
+    tmp.persisted = true;
+    let mut output = match open() {
+        Ok(output) => output,
+        Err(err) => {
+            // the original was not truncated
+            tmp.persisted = false;
+            return Err(ParseError::IoError(err));
+        }
+    };
+
+    match io::copy(&mut tmp.file, &mut output).and_then(|_| output.flush()) {
+        Ok(()) => {
+            tmp.persisted = false;
+            Ok(output)
+        }
+        Err(err) => Err(ParseError::IoError(io::Error::new(
+            err.kind(),
+            format!(
+                "{} (the converted content is kept in {})",
+                err,
+                tmp.path.display()
+            ),
+        ))),
+    }
+}
+
+/// Copies the metadata of the original file to the new file. It fails if any of them cannot be
+/// copied.
+fn copy_metadata(
+    metadata: &Metadata,
+    path: &Path,
+    file: &File,
+    keep_mtime: bool,
+) -> io::Result<()> {
+    copy_ownership(metadata, file)?;
+    copy_xattrs(path, file)?;
+    file.set_permissions(metadata.permissions())?;
+
+    if keep_mtime {
+        file.set_times(file_times(metadata)?)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the access and modification times of the file.
+pub fn file_times(metadata: &Metadata) -> io::Result<FileTimes> {
+    Ok(FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?))
+}
+
+/// Makes the file accessible only by its owner until the permissions of the original are copied,
+/// so that the content is not exposed to others in the meantime.
+#[cfg(unix)]
+fn restrict_permissions(options: &mut OpenOptions) {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    options.mode(0o600);
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_options: &mut OpenOptions) {}
+
+#[cfg(unix)]
+fn has_hard_links(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn has_hard_links(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn copy_ownership(metadata: &Metadata, file: &File) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+
+    let current = file.metadata()?;
+    if current.uid() == metadata.uid() && current.gid() == metadata.gid() {
+        return Ok(());
+    }
+
+    // changing the owner usually requires privileges
+    if unsafe { libc::fchown(file.as_raw_fd(), metadata.uid(), metadata.gid()) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_ownership(_metadata: &Metadata, _file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn copy_xattrs(path: &Path, file: &File) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::AsRawFd;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+
+    let names = read_xattr(|buf| unsafe {
+        libc::listxattr(path.as_ptr(), buf.as_mut_ptr() as *mut _, buf.len())
+    })?;
+
+    for name in names
+        .split(|byte| *byte == 0)
+        .filter(|name| !name.is_empty())
+    {
+        let name = CString::new(name)?;
+        let value = read_xattr(|buf| unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                buf.as_mut_ptr() as *mut _,
+                buf.len(),
+            )
+        })?;
+
+        let result = unsafe {
+            libc::fsetxattr(
+                file.as_raw_fd(),
+                name.as_ptr(),
+                value.as_ptr() as *const _,
+                value.len(),
+                0,
+            )
+        };
+
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a list of extended attributes or a value of one. The size is obtained by the first call
+/// with an empty buffer, it is repeated if the value grows in the meantime.
+#[cfg(target_os = "linux")]
+fn read_xattr<F>(mut call: F) -> io::Result<Vec<u8>>
+where
+    F: FnMut(&mut [u8]) -> libc::ssize_t,
+{
+    loop {
+        let size = call(&mut []);
+        if size < 0 {
+            let err = io::Error::last_os_error();
+            return match err.raw_os_error() {
+                // the file system does not support extended attributes
+                Some(libc::ENOTSUP) => Ok(Vec::new()),
+                _ => Err(err),
+            };
+        }
+
+        let mut buf = vec![0; size as usize];
+        let size = call(&mut buf);
+        if size >= 0 {
+            buf.truncate(size as usize);
+            return Ok(buf);
+        }
+
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ERANGE) {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_xattrs(_path: &Path, _file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+    use std::time::{Duration, SystemTime};
+
+    /// Writer which accepts only a limited number of bytes and then fails.
+    #[derive(Debug)]
+    struct Full(usize);
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::Error::other("no space left"));
+            }
+
+            let n = buf.len().min(self.0);
+            self.0 -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn temp_file(dir: &Path, content: &[u8]) -> TempFile {
+        let mut tmp = TempFile::create(dir, &OsString::from("file.txt")).unwrap();
+        tmp.file.write_all(content).unwrap();
+        tmp
+    }
+
+    fn replace(path: &Path, keep_mtime: bool, content: &[u8]) {
+        replace_file(
+            path,
+            keep_mtime,
+            |file| file.write_all(content).map_err(ParseError::IoError),
+            |_| true,
+        )
+        .unwrap();
+    }
+
+    fn read(path: &Path) -> Vec<u8> {
+        let mut content = Vec::new();
+        File::open(path).unwrap().read_to_end(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn content() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("file.txt");
+        fs::write(&path, b"a\r\n").unwrap();
+
+        replace(&path, false, b"a\n");
+        assert_eq!(read(&path), b"a\n");
+
+        // no temporary file is left behind
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("file.txt");
+        fs::write(&path, b"a\r\n").unwrap();
+
+        let result = replace_file(
+            &path,
+            false,
+            |_| -> Result<(), _> { Err(ParseError::IoError(io::Error::other("failure"))) },
+            |_| true,
+        );
+        assert!(result.is_err());
+        assert_eq!(read(&path), b"a\r\n");
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn copy_back_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tmp = temp_file(dir.path(), b"a\nb\n");
+
+        let output = copy_back(&mut tmp, || Ok(Vec::new())).unwrap();
+        assert_eq!(output, b"a\nb\n");
+
+        drop(tmp);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn copy_back_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tmp = temp_file(dir.path(), b"a\nb\n");
+        let path = tmp.path.clone();
+
+        let err = copy_back(&mut tmp, || Ok(Full(2))).unwrap_err();
+        assert!(err.to_string().contains(&path.display().to_string()));
+
+        // the only complete content is not removed
+        drop(tmp);
+        assert_eq!(read(&path), b"a\nb\n");
+    }
+
+    #[test]
+    fn copy_back_open_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tmp = temp_file(dir.path(), b"a\n");
+
+        let result = copy_back(&mut tmp, || -> io::Result<Vec<u8>> {
+            Err(io::Error::other("denied"))
+        });
+        assert!(result.is_err());
+
+        // the original is untouched, so the content is not needed
+        drop(tmp);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn mtime() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("file.txt");
+        fs::write(&path, b"a\r\n").unwrap();
+
+        let past = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        replace(&path, true, b"a\n");
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), past);
+
+        replace(&path, false, b"b\n");
+        assert_ne!(fs::metadata(&path).unwrap().modified().unwrap(), past);
+    }
+
+    #[test]
+    fn unchanged() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("file.txt");
+        fs::write(&path, b"a\n").unwrap();
+
+        let past = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        let changed = replace_file(
+            &path,
+            false,
+            |file| {
+                file.write_all(b"a\n")
+                    .map(|_| false)
+                    .map_err(ParseError::IoError)
+            },
+            |changed| *changed,
+        )
+        .unwrap();
+
+        assert!(!changed);
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), past);
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("script.sh");
+        fs::write(&path, b"echo\r\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+
+        replace(&path, false, b"echo\n");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("secret.txt");
+        fs::write(&path, b"secret\r\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        replace_file(
+            &path,
+            false,
+            |file| {
+                // the content is not readable by others while it is being written
+                let mode = file.metadata().unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+                file.write_all(b"secret\n").map_err(ParseError::IoError)
+            },
+            |_| true,
+        )
+        .unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(read(&path), b"secret\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("file.txt");
+        let symlink = tmp.path().join("symlink.txt");
+        let hard_link = tmp.path().join("hard_link.txt");
+        fs::write(&path, b"a\r\n").unwrap();
+        std::os::unix::fs::symlink(&path, &symlink).unwrap();
+        fs::hard_link(&path, &hard_link).unwrap();
+
+        replace(&symlink, false, b"a\n");
+        assert!(fs::symlink_metadata(&symlink)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(read(&path), b"a\n");
+        assert_eq!(read(&hard_link), b"a\n");
+    }
+}
@@ -0,0 +1,81 @@
+//! This module provides a heuristic for detecting binary data. Converting line endings in a binary
+//! file (an image, an archive, ...) corrupts it, so such files should be skipped.
+//!
+//! # Examples
+//!
+//! ```
+//! use loe::is_binary;
+//!
+//! assert!(!is_binary(b"hello\r\nworld!\r\n"));
+//! assert!(is_binary(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR"));
+//! ```
+
+/// Number of leading bytes which are inspected by [is_binary](fn.is_binary.html). The value is
+/// the same as git uses.
+pub const BINARY_CHECK_SIZE: usize = 8000;
+
+/// Returns true if the data look like binary. Only the first
+/// [BINARY_CHECK_SIZE](constant.BINARY_CHECK_SIZE.html) bytes are inspected, so it is enough to
+/// pass the beginning of a file.
+///
+/// The data are considered binary if they contain a NUL byte or if more than 1/32 of the bytes are
+/// control characters which do not commonly occur in text files. Whitespace, backspace, bell, form
+/// feed, escape and substitute (used as end-of-file marker by DOS) are not counted. Data starting
+/// with UTF-16 byte order mark are never considered binary.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let bytes = &bytes[0..bytes.len().min(BINARY_CHECK_SIZE)];
+
+    if bytes.starts_with(&[0xff, 0xfe]) || bytes.starts_with(&[0xfe, 0xff]) {
+        return false;
+    }
+
+    if bytes.contains(&0) {
+        return true;
+    }
+
+    let control = bytes
+        .iter()
+        .filter(|byte| is_binary_control(**byte))
+        .count();
+    control * 32 > bytes.len()
+}
+
+fn is_binary_control(byte: u8) -> bool {
+    match byte {
+        // bell, backspace, tab, LF, vertical tab, form feed, CR
+        0x07..=0x0d => false,
+        // substitute, escape
+        0x1a | 0x1b => false,
+        0x00..=0x1f | 0x7f => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text() {
+        assert!(!is_binary(b""));
+        assert!(!is_binary(b"Hello\tworld!\r\n\x0c\x1b[31mred\x1b[0m\n"));
+        assert!(!is_binary("Ahoj světe!".as_bytes()));
+        assert!(!is_binary(b"\xff\xfeA\x00h\x00o\x00j\x00"));
+    }
+
+    #[test]
+    fn binary() {
+        assert!(is_binary(b"Hello\0world!"));
+        assert!(is_binary(b"\x01\x02\x03\x04 some text"));
+    }
+
+    #[test]
+    fn window() {
+        let mut bytes = vec![b'a'; BINARY_CHECK_SIZE];
+        bytes.push(0);
+        assert!(!is_binary(&bytes));
+
+        bytes[BINARY_CHECK_SIZE - 1] = 0;
+        assert!(is_binary(&bytes));
+    }
+}
@@ -0,0 +1,51 @@
+//! Tables of single-byte encodings. All of them are extensions of ASCII, so only the characters for
+//! bytes 0x80 to 0xFF are listed.
+
+/// ISO-8859-1 maps the bytes directly to code points.
+pub(crate) const LATIN1: [char; 128] = latin1();
+
+/// Windows-1252 differs from ISO-8859-1 in the range 0x80 to 0x9F. Undefined bytes 0x81, 0x8D,
+/// 0x8F, 0x90 and 0x9D are mapped to the corresponding C1 control characters.
+pub(crate) const WINDOWS1252: [char; 128] = windows1252();
+
+/// Bytes which are not defined in Windows-1252.
+pub(crate) const WINDOWS1252_UNDEFINED: &[u8] = &[0x81, 0x8d, 0x8f, 0x90, 0x9d];
+
+/// Code page 437 of the original IBM PC.
+#[rustfmt::skip]
+pub(crate) const CP437: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+const fn latin1() -> [char; 128] {
+    let mut table = ['\0'; 128];
+    let mut i = 0;
+    while i < 128 {
+        table[i] = (0x80 + i) as u8 as char;
+        i += 1;
+    }
+    table
+}
+
+const fn windows1252() -> [char; 128] {
+    #[rustfmt::skip]
+    const C1: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+        '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+    ];
+
+    let mut table = latin1();
+    let mut i = 0;
+    while i < 32 {
+        table[i] = C1[i];
+        i += 1;
+    }
+    table
+}
@@ -0,0 +1,363 @@
+use std::fmt;
+use std::io::Write;
+use std::mem;
+use std::ops::Range;
+
+use crate::position::Position;
+use crate::transforms::{Utf16Narrower, Utf16Units};
+use crate::{Bom, CodeUnit, Config, EncodingChecker, ParseError, Report, Transform};
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const UTF16LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16BE_BOM: &[u8] = &[0xfe, 0xff];
+
+/// Incremental converter which does no I/O on its own. The input is fed to it in chunks of
+/// arbitrary sizes and the result is written into given output. It is the state of processing
+/// shared by all entry points, and it can be used directly where the input is not available as a
+/// reader, for example, when the data come from an asynchronous stream.
+///
+/// # Examples
+///
+/// ```
+/// use loe::{Config, Converter};
+///
+/// let mut converter = Converter::new(Config::default());
+/// let mut output = Vec::new();
+///
+/// // the chunks would be received from a socket, for example
+/// for chunk in &[&b"hello\r"[..], &b"\nworld!\r"[..], &b"\n"[..]] {
+///     converter.convert(chunk, &mut output).unwrap();
+///     // the output can be written and cleared here
+/// }
+///
+/// let report = converter.finish(&mut output).unwrap();
+/// assert_eq!(output, b"hello\nworld!\n");
+/// assert_eq!(report.converted(), 2);
+/// ```
+pub struct Converter {
+    encoding_name: String,
+    encoding: Box<dyn EncodingChecker>,
+    transform: Box<dyn Transform>,
+    code_unit: CodeUnit,
+    /// Characters of a single-byte encoding if the input is converted to UTF-8.
+    table: Option<&'static [char; 128]>,
+    bom: Bom,
+    /// The beginning of the input which is held back until it is known whether it starts with a
+    /// byte order mark. It is `None` once this is resolved.
+    head: Option<Vec<u8>>,
+    /// The beginning of the input which is held back until the transform has seen enough of it.
+    /// It is `None` if the transform does not need it or once it is prepared.
+    lookahead: Option<Vec<u8>>,
+    lookahead_size: usize,
+    position: Position,
+    /// Position of the first byte of the character which is pending in the encoding checker.
+    pending_start: Position,
+    report: Report,
+    /// Reader of code units for counting line endings in UTF-16 input.
+    units: Option<Utf16Narrower>,
+    decode_buffer: Vec<u8>,
+    narrow_buffer: Vec<u8>,
+    write_buffer: Vec<u8>,
+}
+
+impl Converter {
+    /// Creates the converter for given config.
+    pub fn new<E, T>(config: Config<E, T>) -> Self
+    where
+        E: Into<Box<dyn EncodingChecker>> + fmt::Display,
+        T: Into<Box<dyn Transform>>,
+    {
+        let encoding_name = format!("{}", config.encoding_checker);
+        let encoding: Box<dyn EncodingChecker> = config.encoding_checker.into();
+        let mut transform: Box<dyn Transform> = config.transform_mode.into();
+
+        let code_unit = encoding.code_unit();
+        let units = if code_unit != CodeUnit::Byte {
+            transform = Box::new(Utf16Units::new(transform, code_unit));
+            Some(Utf16Narrower::new(code_unit))
+        } else {
+            None
+        };
+
+        let lookahead = if transform.needs_lookahead() {
+            Some(Vec::new())
+        } else {
+            None
+        };
+
+        let report = Report {
+            target: transform.target(),
+            ..Report::default()
+        };
+
+        let table = if config.transcode {
+            encoding.single_byte_table()
+        } else {
+            None
+        };
+
+        Converter {
+            encoding_name,
+            encoding,
+            transform,
+            code_unit,
+            table,
+            bom: config.bom,
+            head: Some(Vec::new()),
+            lookahead,
+            lookahead_size: config.lookahead,
+            position: Position::with_code_unit(code_unit),
+            pending_start: Position::with_code_unit(code_unit),
+            report,
+            units,
+            decode_buffer: Vec::new(),
+            narrow_buffer: Vec::new(),
+            write_buffer: Vec::new(),
+        }
+    }
+
+    /// Marks that the input does not start at its beginning, so the byte order mark policy is not
+    /// applied.
+    pub(crate) fn skip_head(&mut self) {
+        self.head = None;
+    }
+
+    /// Converts next chunk of the input and writes the result into the output. Nothing may be
+    /// written for some chunks, for example, when the beginning of the input is held back until it
+    /// is known whether it starts with a byte order mark.
+    pub fn convert<O: Write>(&mut self, input: &[u8], output: &mut O) -> Result<(), ParseError> {
+        self.check(input)?;
+        self.report.bytes_read += input.len() as u64;
+
+        match self.table {
+            Some(table) => {
+                // characters outside ASCII are encoded differently in UTF-8
+                if !input.is_ascii() {
+                    self.report.changed = true;
+                }
+
+                let mut decoded = mem::take(&mut self.decode_buffer);
+                decoded.clear();
+                decode(table, input, &mut decoded);
+
+                let result = self.look_ahead(&decoded, output);
+                self.decode_buffer = decoded;
+                result
+            }
+            None => self.look_ahead(input, output),
+        }
+    }
+
+    /// Holds back the beginning of the input until it is long enough for preparing the transform.
+    fn look_ahead<O: Write>(&mut self, input: &[u8], output: &mut O) -> Result<(), ParseError> {
+        match self.lookahead.take() {
+            Some(mut buffered) => {
+                buffered.extend_from_slice(input);
+                if buffered.len() < self.lookahead_size {
+                    self.lookahead = Some(buffered);
+                    return Ok(());
+                }
+
+                self.prepare(&buffered[0..self.lookahead_size]);
+                self.convert_checked(&buffered, output)
+            }
+            None => self.convert_checked(input, output),
+        }
+    }
+
+    fn prepare(&mut self, head: &[u8]) {
+        self.transform.prepare(head);
+        // the line ending may be decided by the preparation
+        self.report.target = self.transform.target();
+    }
+
+    /// Converts the input which was already checked for encoding errors (and decoded into UTF-8
+    /// if requested).
+    fn convert_checked<O: Write>(
+        &mut self,
+        input: &[u8],
+        output: &mut O,
+    ) -> Result<(), ParseError> {
+        let input = match self.head.take() {
+            Some(mut head) => {
+                let len = self
+                    .max_bom_len()
+                    .saturating_sub(head.len())
+                    .min(input.len());
+                head.extend_from_slice(&input[0..len]);
+
+                if self.is_undecided(&head) {
+                    self.head = Some(head);
+                    return Ok(());
+                }
+
+                self.resolve_head(&head, output)?;
+                &input[len..]
+            }
+            None => input,
+        };
+
+        let len = self.transform(input);
+        if self.report.target.is_none() && self.write_buffer[0..len] != *input {
+            self.report.changed = true;
+        }
+
+        self.write(0..len, output)
+    }
+
+    /// Finishes the processing, writes the rest of the result and returns the report of the whole
+    /// processing. It must be called after all the input was converted.
+    pub fn finish<O: Write>(&mut self, output: &mut O) -> Result<Report, ParseError> {
+        if let Some(buffered) = self.lookahead.take() {
+            self.prepare(&buffered);
+            self.convert_checked(&buffered, output)?;
+        }
+
+        if let Some(head) = self.head.take() {
+            self.resolve_head(&head, output)?;
+        }
+
+        if !self.encoding.finish() {
+            return Err(ParseError::IncompleteEncoding {
+                encoding: self.encoding_name.clone(),
+                offset: self.pending_start.offset(),
+                line: self.pending_start.line(),
+                column: self.pending_start.column(),
+                sequence: self.encoding.pending().to_vec(),
+            });
+        }
+
+        Ok(self.report.clone())
+    }
+
+    /// Feeds the encoding checker and tracks the position in the input.
+    fn check(&mut self, input: &[u8]) -> Result<(), ParseError> {
+        if let Err(i) = self.encoding.feed_slice(input) {
+            let mut sequence = self.encoding.pending().to_vec();
+            sequence.push(input[i]);
+            self.position.advance(&input[0..i]);
+
+            return Err(ParseError::InvalidEncoding {
+                encoding: self.encoding_name.clone(),
+                offset: self.position.offset(),
+                line: self.position.line(),
+                column: self.position.column(),
+                sequence,
+            });
+        }
+
+        // the pending character may have started in one of the previous chunks
+        let pending = self.encoding.pending().len();
+        if pending > 0 && pending <= input.len() {
+            let start = input.len() - pending;
+            self.position.advance(&input[0..start]);
+            self.pending_start = self.position.clone();
+            self.position.advance(&input[start..]);
+        } else {
+            self.position.advance(input);
+        }
+
+        Ok(())
+    }
+
+    /// Transforms the input into the write buffer and returns the number of written bytes.
+    fn transform(&mut self, input: &[u8]) -> usize {
+        self.count(input);
+
+        // UTF-16 code unit can be split between two chunks, so one byte from the previous chunk
+        // may be written together with this one
+        let len = 2 * (input.len() + 1);
+        if self.write_buffer.len() < len {
+            self.write_buffer.resize(len, 0);
+        }
+
+        self.transform
+            .transform_slice(input, &mut self.write_buffer)
+    }
+
+    /// Counts the line endings in the input before it is transformed.
+    fn count(&mut self, input: &[u8]) {
+        match self.units {
+            Some(ref mut units) => {
+                self.narrow_buffer.clear();
+                self.narrow_buffer.extend(
+                    input
+                        .iter()
+                        .filter_map(|&byte| units.push(byte).map(|(narrow, _)| narrow)),
+                );
+                self.report.line_endings.feed(&self.narrow_buffer);
+            }
+            None => self.report.line_endings.feed(input),
+        }
+    }
+
+    fn write<O: Write>(&mut self, range: Range<usize>, output: &mut O) -> Result<(), ParseError> {
+        self.report.bytes_written += range.len() as u64;
+        output
+            .write_all(&self.write_buffer[range])
+            .map_err(ParseError::write_error)
+    }
+
+    /// Returns the byte order marks which can occur at the beginning of the input.
+    fn boms(&self) -> &'static [&'static [u8]] {
+        match self.code_unit {
+            CodeUnit::Byte => &[UTF8_BOM],
+            CodeUnit::Utf16 => &[UTF16LE_BOM, UTF16BE_BOM],
+            CodeUnit::Utf16Le => &[UTF16LE_BOM],
+            CodeUnit::Utf16Be => &[UTF16BE_BOM],
+        }
+    }
+
+    fn max_bom_len(&self) -> usize {
+        self.boms().iter().map(|bom| bom.len()).max().unwrap_or(0)
+    }
+
+    /// Returns true if the head is too short to decide whether it starts with a byte order mark.
+    fn is_undecided(&self, head: &[u8]) -> bool {
+        self.boms()
+            .iter()
+            .any(|bom| head.len() < bom.len() && bom.starts_with(head))
+    }
+
+    /// Transforms the beginning of the input and applies the byte order mark policy on it. The
+    /// byte order mark is passed through the transform so it can detect the byte order from it.
+    fn resolve_head<O: Write>(&mut self, head: &[u8], output: &mut O) -> Result<(), ParseError> {
+        let present = self.boms().iter().find(|bom| head.starts_with(bom));
+        let len = self.transform(head);
+
+        match (self.bom, present) {
+            (Bom::Remove, Some(bom)) => {
+                self.report.changed = true;
+                self.write(bom.len()..len, output)
+            }
+            (Bom::Add, None) => {
+                // without byte order mark, UTF-16 is assumed to be little-endian
+                let bom = match self.code_unit {
+                    CodeUnit::Byte => UTF8_BOM,
+                    CodeUnit::Utf16Be => UTF16BE_BOM,
+                    CodeUnit::Utf16 | CodeUnit::Utf16Le => UTF16LE_BOM,
+                };
+
+                output.write_all(bom).map_err(ParseError::write_error)?;
+                self.report.bytes_written += bom.len() as u64;
+                self.report.changed = true;
+                self.write(0..len, output)
+            }
+            _ => self.write(0..len, output),
+        }
+    }
+}
+
+/// Converts the input in single-byte encoding given by its table into UTF-8.
+fn decode(table: &[char; 128], input: &[u8], output: &mut Vec<u8>) {
+    let mut buffer = [0; 4];
+
+    for &byte in input {
+        if byte < 0x80 {
+            output.push(byte);
+        } else {
+            let c = table[usize::from(byte - 0x80)];
+            output.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+        }
+    }
+}
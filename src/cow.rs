@@ -0,0 +1,185 @@
+//! This module provides convenient conversion of data in memory. The input is borrowed in the
+//! result if no change is needed.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::borrow::Cow;
+//!
+//! use loe::{convert_str, TransformMode};
+//!
+//! assert_eq!(convert_str("hello\r\nworld!\r\n", TransformMode::Lf), "hello\nworld!\n");
+//!
+//! // no allocation is needed
+//! assert!(matches!(convert_str("hello\nworld!\n", TransformMode::Lf), Cow::Borrowed(_)));
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::transforms::{CR_CHAR, LF_CHAR};
+use crate::{
+    process_slice, Config, EncodingChecker, LineEndingStats, ParseError, Transform, TransformMode,
+};
+
+/// Converts the line endings in the string. The string is returned as borrowed if it already uses
+/// the requested line ending everywhere. For [TransformMode::Auto](enum.TransformMode.html#variant.Auto),
+/// the whole string is inspected.
+pub fn convert_str(input: &str, mode: TransformMode) -> Cow<'_, str> {
+    if is_conforming(input.as_bytes(), mode) {
+        return Cow::Borrowed(input);
+    }
+
+    let config = Config::default()
+        .transform(mode)
+        .lookahead(input.len().max(1));
+
+    match convert_bytes(input.as_bytes(), config) {
+        // only line endings are replaced, so the string stays valid UTF-8
+        Ok(Cow::Owned(bytes)) => Cow::Owned(String::from_utf8(bytes).unwrap()),
+        _ => unreachable!("conversion of a non-conforming string without checks"),
+    }
+}
+
+/// Converts the bytes according to the config. The input is returned as borrowed if the conversion
+/// does not change it. The encoding is checked the same way as in [process](fn.process.html).
+///
+/// # Examples
+///
+/// ```
+/// use loe::{convert_bytes, Config, Encoding};
+///
+/// let config = Config::default().encoding(Encoding::Ascii);
+/// assert_eq!(&*convert_bytes(b"hello\r\n", config.clone()).unwrap(), b"hello\n");
+/// assert!(convert_bytes("světe\r\n".as_bytes(), config).is_err());
+/// ```
+pub fn convert_bytes<E, T>(input: &[u8], config: Config<E, T>) -> Result<Cow<'_, [u8]>, ParseError>
+where
+    E: Into<Box<dyn EncodingChecker>> + fmt::Display,
+    T: Into<Box<dyn Transform>>,
+{
+    let mut output = CowWriter::new(input);
+    process_slice(input, &mut output, config)?;
+    Ok(output.into_cow())
+}
+
+/// Returns true if the input uses given line ending everywhere.
+fn is_conforming(input: &[u8], mode: TransformMode) -> bool {
+    match mode {
+        TransformMode::Native => is_conforming(input, TransformMode::native()),
+        TransformMode::Auto => {
+            let mut stats = LineEndingStats::new();
+            stats.feed(input);
+            !stats.is_mixed()
+        }
+        TransformMode::Lf => !input.contains(&CR_CHAR),
+        TransformMode::Cr => !input.contains(&LF_CHAR),
+        TransformMode::Crlf => input.iter().enumerate().all(|(i, byte)| match *byte {
+            LF_CHAR => i > 0 && input[i - 1] == CR_CHAR,
+            CR_CHAR => input.get(i + 1) == Some(&LF_CHAR),
+            _ => true,
+        }),
+    }
+}
+
+/// Writer which compares the output with the input and copies it only once they differ.
+struct CowWriter<'a> {
+    input: &'a [u8],
+    /// Length of the output while it is the same as the beginning of the input.
+    len: usize,
+    owned: Option<Vec<u8>>,
+}
+
+impl<'a> CowWriter<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        CowWriter {
+            input,
+            len: 0,
+            owned: None,
+        }
+    }
+
+    fn into_cow(self) -> Cow<'a, [u8]> {
+        match self.owned {
+            Some(owned) => Cow::Owned(owned),
+            None => Cow::Borrowed(&self.input[0..self.len]),
+        }
+    }
+}
+
+impl Write for CowWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.owned {
+            Some(ref mut owned) => owned.extend_from_slice(buf),
+            None if self.input[self.len..].starts_with(buf) => self.len += buf.len(),
+            None => {
+                let mut owned = Vec::with_capacity(self.input.len() + buf.len());
+                owned.extend_from_slice(&self.input[0..self.len]);
+                owned.extend_from_slice(buf);
+                self.owned = Some(owned);
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bom, Encoding};
+
+    #[test]
+    fn str_borrowed() {
+        for &(input, mode) in &[
+            ("", TransformMode::Lf),
+            ("hello\nworld!\n", TransformMode::Lf),
+            ("hello\r\nworld!\r\n", TransformMode::Crlf),
+            ("hello\rworld!\r", TransformMode::Cr),
+            ("hello\r\nworld!\r\n", TransformMode::Auto),
+        ] {
+            let output = convert_str(input, mode);
+            assert!(matches!(output, Cow::Borrowed(_)));
+            assert_eq!(output, input);
+        }
+    }
+
+    #[test]
+    fn str_owned() {
+        for &(input, mode, expected) in &[
+            ("hello\r\nworld!\r\n", TransformMode::Lf, "hello\nworld!\n"),
+            ("světe\n", TransformMode::Crlf, "světe\r\n"),
+            ("a\r\nb\rc", TransformMode::Crlf, "a\r\nb\r\nc"),
+            ("a\r\nb\n", TransformMode::Cr, "a\rb\r"),
+            ("a\r\nb\nc\r\n", TransformMode::Auto, "a\r\nb\r\nc\r\n"),
+        ] {
+            let output = convert_str(input, mode);
+            assert!(matches!(output, Cow::Owned(_)));
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        let output = convert_bytes(b"hello\nworld!\n", Config::default()).unwrap();
+        assert!(matches!(output, Cow::Borrowed(_)));
+
+        // removing the byte order mark changes the beginning
+        let config = Config::default().bom(Bom::Remove);
+        let output = convert_bytes(b"\xef\xbb\xbfhello\n", config).unwrap();
+        assert!(matches!(output, Cow::Owned(_)));
+        assert_eq!(&*output, b"hello\n");
+
+        let output = convert_bytes(b"hello\r\n", Config::default()).unwrap();
+        assert!(matches!(output, Cow::Owned(_)));
+        assert_eq!(&*output, b"hello\n");
+
+        let config = Config::default().encoding(Encoding::Utf8);
+        assert!(convert_bytes(b"hello\xff\n", config).is_err());
+    }
+}
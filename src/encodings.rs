@@ -17,6 +17,9 @@
 //! ```
 
 use std::fmt;
+use std::str;
+
+use crate::codepages;
 
 /// Enumeration of core-supported encodings.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -25,8 +28,22 @@ pub enum Encoding {
     Ignore,
     /// Ascii encoding, that is, each byte has to be less than 128.
     Ascii,
-    /// Valid UTF-8 encoding.
+    /// Valid UTF-8 encoding. A leading byte order mark is accepted and can be kept, removed or
+    /// added according to [Bom](enum.Bom.html) policy.
     Utf8,
+    /// Valid UTF-16 encoding with byte order detected from the byte order mark. If there is no
+    /// byte order mark, little-endian is assumed.
+    Utf16,
+    /// Valid UTF-16 little-endian encoding.
+    Utf16Le,
+    /// Valid UTF-16 big-endian encoding.
+    Utf16Be,
+    /// ISO-8859-1 (Latin-1) encoding, in which every byte is valid.
+    Latin1,
+    /// Windows-1252 encoding. Bytes 0x81, 0x8D, 0x8F, 0x90 and 0x9D are invalid.
+    Windows1252,
+    /// Code page 437 of the original IBM PC, in which every byte is valid.
+    Cp437,
 }
 
 impl From<Encoding> for Box<dyn EncodingChecker> {
@@ -35,6 +52,15 @@ impl From<Encoding> for Box<dyn EncodingChecker> {
             Encoding::Ignore => Box::new(Ignore::new()),
             Encoding::Ascii => Box::new(Ascii::new()),
             Encoding::Utf8 => Box::new(Utf8::new()),
+            Encoding::Utf16 => Box::new(Utf16::new(CodeUnit::Utf16)),
+            Encoding::Utf16Le => Box::new(Utf16::new(CodeUnit::Utf16Le)),
+            Encoding::Utf16Be => Box::new(Utf16::new(CodeUnit::Utf16Be)),
+            Encoding::Latin1 => Box::new(SingleByte::new(&codepages::LATIN1, &[])),
+            Encoding::Windows1252 => Box::new(SingleByte::new(
+                &codepages::WINDOWS1252,
+                codepages::WINDOWS1252_UNDEFINED,
+            )),
+            Encoding::Cp437 => Box::new(SingleByte::new(&codepages::CP437, &[])),
         }
     }
 }
@@ -44,6 +70,12 @@ impl fmt::Display for Encoding {
         let name = match self {
             Encoding::Utf8 => "UTF-8",
             Encoding::Ascii => "Ascii",
+            Encoding::Utf16 => "UTF-16",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Latin1 => "ISO-8859-1",
+            Encoding::Windows1252 => "Windows-1252",
+            Encoding::Cp437 => "CP437",
             Encoding::Ignore => "<none>",
         };
 
@@ -51,13 +83,80 @@ impl fmt::Display for Encoding {
     }
 }
 
+/// Code units in which line endings are encoded. The transforms operate on single bytes by
+/// default, 16-bit code units are handled by wrapping the transform so that line endings are
+/// recognized and written as whole units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodeUnit {
+    /// Single byte, used by ASCII compatible encodings.
+    Byte,
+    /// 16-bit unit with byte order detected from the byte order mark (little-endian if there is
+    /// none).
+    Utf16,
+    /// 16-bit little-endian unit.
+    Utf16Le,
+    /// 16-bit big-endian unit.
+    Utf16Be,
+}
+
+impl CodeUnit {
+    /// Resolves the byte order from the first two bytes of the input. Returns true for big-endian.
+    pub(crate) fn is_big_endian(self, first: [u8; 2]) -> bool {
+        match self {
+            CodeUnit::Utf16Be => true,
+            CodeUnit::Utf16 => first == [0xfe, 0xff],
+            CodeUnit::Byte | CodeUnit::Utf16Le => false,
+        }
+    }
+}
+
 /// Trait used for encoding checking. It should behave like a state machine to which bytes are fed.
 /// If the passed bytes causes the checker to enter an invalid state, the method should return
 /// false as the indication.
 pub trait EncodingChecker {
-    /// The only method of the checker. It gets the current byte of the input and returns if it is
+    /// The main method of the checker. It gets the current byte of the input and returns if it is
     /// still valid encoding.
     fn feed(&mut self, byte: u8) -> bool;
+
+    /// Feeds a whole slice of the input. It returns the index of the first invalid byte in case of
+    /// an error, the state of the checker must be then the same as if the bytes were fed one by
+    /// one. The default implementation calls [feed](#tymethod.feed) for every byte, checkers
+    /// should override it if they can validate larger chunks at once.
+    fn feed_slice(&mut self, input: &[u8]) -> Result<(), usize> {
+        for (i, byte) in input.iter().enumerate() {
+            if !self.feed(*byte) {
+                return Err(i);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the bytes of a character which was started but is not complete yet. It is used for
+    /// error reporting only. The default implementation returns an empty slice.
+    fn pending(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Called when the end of the input is reached. It returns false if the input is not valid
+    /// at its end, for example, because it ends in the middle of a multi-byte character. The
+    /// default implementation returns true.
+    fn finish(&mut self) -> bool {
+        true
+    }
+
+    /// Returns the code units in which line endings are encoded. The default implementation
+    /// returns `CodeUnit::Byte`.
+    fn code_unit(&self) -> CodeUnit {
+        CodeUnit::Byte
+    }
+
+    /// Returns the characters represented by bytes 0x80 to 0xFF if the encoding is a single-byte
+    /// extension of ASCII. It is used for converting the input to UTF-8. The default
+    /// implementation returns `None`.
+    fn single_byte_table(&self) -> Option<&'static [char; 128]> {
+        None
+    }
 }
 
 struct Ignore;
@@ -72,6 +171,10 @@ impl EncodingChecker for Ignore {
     fn feed(&mut self, _byte: u8) -> bool {
         true
     }
+
+    fn feed_slice(&mut self, _input: &[u8]) -> Result<(), usize> {
+        Ok(())
+    }
 }
 
 struct Ascii;
@@ -86,50 +189,216 @@ impl EncodingChecker for Ascii {
     fn feed(&mut self, byte: u8) -> bool {
         byte < 128
     }
+
+    fn feed_slice(&mut self, input: &[u8]) -> Result<(), usize> {
+        // the standard library checks whole words at once
+        if input.is_ascii() {
+            Ok(())
+        } else {
+            Err(input.iter().position(|byte| !byte.is_ascii()).unwrap())
+        }
+    }
 }
 
+/// Checker of UTF-8 encoding which conforms to the table of well-formed byte sequences in the
+/// Unicode standard. Overlong encodings, encoded surrogates and code points above U+10FFFF are
+/// rejected.
 struct Utf8 {
-    counter: Option<u8>,
+    /// Number of continuation bytes which are still expected.
+    remaining: u8,
+    /// Allowed range of the next continuation byte.
+    lower: u8,
+    upper: u8,
+    bytes: [u8; 4],
+    len: usize,
 }
 
 impl Utf8 {
     fn new() -> Self {
-        Utf8 { counter: None }
+        Utf8 {
+            remaining: 0,
+            lower: 0x80,
+            upper: 0xbf,
+            bytes: [0; 4],
+            len: 0,
+        }
     }
 }
 
 impl EncodingChecker for Utf8 {
     fn feed(&mut self, byte: u8) -> bool {
-        let counter = match self.counter {
-            Some(counter) => {
-                if byte & 0xc0 == 0x80 {
-                    if counter == 0 {
-                        None
-                    } else {
-                        Some(counter - 1)
-                    }
-                } else {
-                    return false;
+        if self.remaining > 0 {
+            if byte < self.lower || byte > self.upper {
+                return false;
+            }
+
+            self.remaining -= 1;
+            self.lower = 0x80;
+            self.upper = 0xbf;
+        } else {
+            let (remaining, lower, upper) = match byte {
+                0x00..=0x7f => (0, 0x80, 0xbf),
+                0xc2..=0xdf => (1, 0x80, 0xbf),
+                0xe0 => (2, 0xa0, 0xbf),
+                0xe1..=0xec | 0xee..=0xef => (2, 0x80, 0xbf),
+                0xed => (2, 0x80, 0x9f),
+                0xf0 => (3, 0x90, 0xbf),
+                0xf1..=0xf3 => (3, 0x80, 0xbf),
+                0xf4 => (3, 0x80, 0x8f),
+                _ => return false,
+            };
+
+            self.remaining = remaining;
+            self.lower = lower;
+            self.upper = upper;
+            self.len = 0;
+        }
+
+        if self.remaining > 0 {
+            self.bytes[self.len] = byte;
+            self.len += 1;
+        } else {
+            self.len = 0;
+        }
+
+        true
+    }
+
+    fn feed_slice(&mut self, input: &[u8]) -> Result<(), usize> {
+        // complete the character started in the previous slice
+        let mut start = 0;
+        while self.remaining > 0 && start < input.len() {
+            if !self.feed(input[start]) {
+                return Err(start);
+            }
+            start += 1;
+        }
+
+        // the validation in the standard library is much faster and it accepts exactly the same
+        // sequences, it is only needed to find the exact place and the state in case of an error
+        // or a character incomplete at the end
+        if let Err(err) = str::from_utf8(&input[start..]) {
+            let start = start + err.valid_up_to();
+            for (i, byte) in input[start..].iter().enumerate() {
+                if !self.feed(*byte) {
+                    return Err(start + i);
                 }
             }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> bool {
+        self.remaining == 0
+    }
+
+    fn pending(&self) -> &[u8] {
+        &self.bytes[0..self.len]
+    }
+}
+
+/// Checker of UTF-16 encoding. Unpaired surrogates are rejected.
+struct Utf16 {
+    code_unit: CodeUnit,
+    big_endian: Option<bool>,
+    /// The first byte of the current code unit.
+    first: Option<u8>,
+    /// Whether a high surrogate was read and a low surrogate is expected.
+    high: bool,
+    bytes: [u8; 4],
+    len: usize,
+}
+
+impl Utf16 {
+    fn new(code_unit: CodeUnit) -> Self {
+        Utf16 {
+            code_unit,
+            big_endian: None,
+            first: None,
+            high: false,
+            bytes: [0; 4],
+            len: 0,
+        }
+    }
+}
+
+impl EncodingChecker for Utf16 {
+    fn feed(&mut self, byte: u8) -> bool {
+        let first = match self.first.take() {
+            Some(first) => first,
             None => {
-                if byte & 0x80 == 0 {
-                    None
-                } else if byte & 0xe0 == 0xc0 {
-                    Some(0)
-                } else if byte & 0xf0 == 0xe0 {
-                    Some(1)
-                } else if byte & 0xf8 == 0xf0 {
-                    Some(2)
-                } else {
-                    return false;
-                }
+                self.first = Some(byte);
+                self.bytes[self.len] = byte;
+                self.len += 1;
+                return true;
             }
         };
 
-        self.counter = counter;
+        let code_unit = self.code_unit;
+        let big_endian = *self
+            .big_endian
+            .get_or_insert_with(|| code_unit.is_big_endian([first, byte]));
+
+        let unit = if big_endian {
+            u16::from_be_bytes([first, byte])
+        } else {
+            u16::from_le_bytes([first, byte])
+        };
+
+        let is_high = (0xd800..=0xdbff).contains(&unit);
+        let is_low = (0xdc00..=0xdfff).contains(&unit);
+
+        if self.high != is_low {
+            // unpaired surrogate
+            return false;
+        }
+
+        self.high = is_high;
+
+        if self.high {
+            self.bytes[self.len] = byte;
+            self.len += 1;
+        } else {
+            self.len = 0;
+        }
+
         true
     }
+
+    fn pending(&self) -> &[u8] {
+        &self.bytes[0..self.len]
+    }
+
+    fn finish(&mut self) -> bool {
+        self.first.is_none() && !self.high
+    }
+
+    fn code_unit(&self) -> CodeUnit {
+        self.code_unit
+    }
+}
+
+/// Checker of single-byte encodings which extend ASCII.
+struct SingleByte {
+    table: &'static [char; 128],
+    undefined: &'static [u8],
+}
+
+impl SingleByte {
+    fn new(table: &'static [char; 128], undefined: &'static [u8]) -> Self {
+        SingleByte { table, undefined }
+    }
+}
+
+impl EncodingChecker for SingleByte {
+    fn feed(&mut self, byte: u8) -> bool {
+        !self.undefined.contains(&byte)
+    }
+
+    fn single_byte_table(&self) -> Option<&'static [char; 128]> {
+        Some(self.table)
+    }
 }
 
 #[cfg(test)]
@@ -165,15 +434,175 @@ mod tests {
         feed_valid(&mut Utf8::new(), "Ahoj světe!".as_bytes());
         feed_valid(
             &mut Utf8::new(),
-            &[0xc0, 0x80, 0xe0, 0x80, 0x80, 0xf0, 0x80, 0x80, 0x80],
+            &[0xc2, 0x80, 0xe0, 0xa0, 0x80, 0xf0, 0x90, 0x80, 0x80],
         );
         feed_valid(
             &mut Utf8::new(),
-            &[0xc0, 0xbf, 0xe0, 0xbf, 0xbf, 0xf0, 0xbf, 0xbf, 0xbf],
+            &[0xdf, 0xbf, 0xef, 0xbf, 0xbf, 0xf4, 0x8f, 0xbf, 0xbf],
         );
+        feed_valid(&mut Utf8::new(), &[0xed, 0x9f, 0xbf, 0xee, 0x80, 0x80]);
 
         feed_invalid(&mut Utf8::new(), &[0x80]);
-        feed_invalid(&mut Utf8::new(), &[0xc0, 0x7f]);
-        feed_invalid(&mut Utf8::new(), &[0xc0, 0x80, 0x80]);
+        feed_invalid(&mut Utf8::new(), &[0xc2, 0x7f]);
+        feed_invalid(&mut Utf8::new(), &[0xc2, 0x80, 0x80]);
+        feed_invalid(&mut Utf8::new(), &[0xf8, 0x80, 0x80, 0x80, 0x80]);
+    }
+
+    #[test]
+    fn utf8_overlong() {
+        feed_invalid(&mut Utf8::new(), &[0xc0, 0x80]);
+        feed_invalid(&mut Utf8::new(), &[0xc1, 0xbf]);
+        feed_invalid(&mut Utf8::new(), &[0xe0, 0x80, 0x80]);
+        feed_invalid(&mut Utf8::new(), &[0xe0, 0x9f, 0xbf]);
+        feed_invalid(&mut Utf8::new(), &[0xf0, 0x80, 0x80, 0x80]);
+        feed_invalid(&mut Utf8::new(), &[0xf0, 0x8f, 0xbf, 0xbf]);
+    }
+
+    #[test]
+    fn utf8_surrogates() {
+        feed_invalid(&mut Utf8::new(), &[0xed, 0xa0, 0x80]);
+        feed_invalid(&mut Utf8::new(), &[0xed, 0xbf, 0xbf]);
+    }
+
+    #[test]
+    fn utf8_out_of_range() {
+        feed_invalid(&mut Utf8::new(), &[0xf4, 0x90, 0x80, 0x80]);
+        feed_invalid(&mut Utf8::new(), &[0xf5, 0x80, 0x80, 0x80]);
+        feed_invalid(&mut Utf8::new(), &[0xff]);
+    }
+
+    #[test]
+    fn utf8_finish() {
+        let mut utf8 = Utf8::new();
+        feed_valid(&mut utf8, "Ahoj".as_bytes());
+        assert!(utf8.finish());
+
+        let mut utf8 = Utf8::new();
+        feed_valid(&mut utf8, &[b'a', 0xe2, 0x82]);
+        assert!(!utf8.finish());
+
+        let mut utf8 = Utf8::new();
+        feed_valid(&mut utf8, &[0xe2, 0x82, 0xac]);
+        assert!(utf8.finish());
+    }
+
+    #[test]
+    fn utf16() {
+        let mut utf16 = Utf16::new(CodeUnit::Utf16Le);
+        feed_valid(&mut utf16, &[b'a', 0, b'\r', 0, b'\n', 0]);
+        assert!(utf16.finish());
+
+        let mut utf16 = Utf16::new(CodeUnit::Utf16Be);
+        feed_valid(&mut utf16, &[0, b'a', 0xd8, 0x3d, 0xde, 0x00]);
+        assert!(utf16.finish());
+
+        feed_invalid(&mut Utf16::new(CodeUnit::Utf16Le), &[0x00, 0xdc]);
+        feed_invalid(&mut Utf16::new(CodeUnit::Utf16Le), &[0x3d, 0xd8, b'a', 0]);
+        feed_invalid(&mut Utf16::new(CodeUnit::Utf16Be), &[0xd8, 0x3d, 0, b'a']);
+
+        let mut utf16 = Utf16::new(CodeUnit::Utf16Le);
+        feed_valid(&mut utf16, &[b'a', 0, 0x3d, 0xd8, 0x00]);
+        assert_eq!(utf16.pending(), &[0x3d, 0xd8, 0x00]);
+        assert!(!utf16.finish());
+    }
+
+    #[test]
+    fn utf16_bom() {
+        // lone low surrogate in little-endian, valid character in big-endian
+        let bytes = [0x00, 0xdc];
+
+        let mut utf16 = Utf16::new(CodeUnit::Utf16);
+        feed_valid(&mut utf16, &[0xfe, 0xff]);
+        feed_valid(&mut utf16, &bytes);
+
+        let mut utf16 = Utf16::new(CodeUnit::Utf16);
+        feed_invalid(&mut utf16, &[0xff, 0xfe, 0x00, 0xdc]);
+
+        feed_invalid(&mut Utf16::new(CodeUnit::Utf16), &bytes);
+    }
+
+    #[test]
+    fn single_byte() {
+        let all = (0..=255).collect::<Vec<u8>>();
+        feed_valid(&mut SingleByte::new(&codepages::LATIN1, &[]), &all);
+        feed_valid(&mut SingleByte::new(&codepages::CP437, &[]), &all);
+
+        let mut windows1252 =
+            SingleByte::new(&codepages::WINDOWS1252, codepages::WINDOWS1252_UNDEFINED);
+        feed_valid(&mut windows1252, b"Hello \x80\x9f\xff");
+        feed_invalid(&mut windows1252, &[0x81]);
+        feed_invalid(&mut windows1252, &[0x9d]);
+    }
+
+    #[test]
+    fn std_agreement() {
+        // all two-byte and three-byte sequences (and a sample of four-byte ones) are accepted
+        // exactly when the standard library accepts them
+        for first in 0x80..=0xffu8 {
+            for second in 0..=0xffu8 {
+                for third in [0x00, 0x80, 0x9f, 0xa0, 0xbf, 0xc0].iter() {
+                    for fourth in [0x00, 0x80, 0xbf].iter() {
+                        let bytes = [first, second, *third, *fourth];
+                        for len in 2..=4 {
+                            let bytes = &bytes[0..len];
+                            let mut utf8 = Utf8::new();
+                            let valid = bytes.iter().all(|byte| utf8.feed(*byte)) && utf8.finish();
+                            assert_eq!(valid, std::str::from_utf8(bytes).is_ok(), "{:x?}", bytes);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn utf8_pending() {
+        let mut utf8 = Utf8::new();
+        feed_valid(&mut utf8, &[b'a', 0xf0, 0x90]);
+        assert_eq!(utf8.pending(), &[0xf0, 0x90]);
+        feed_valid(&mut utf8, &[0x80, 0x80]);
+        assert_eq!(utf8.pending(), &[]);
+    }
+
+    #[test]
+    fn feed_slice() {
+        let inputs: &[&[u8]] = &[
+            b"Hello world!",
+            "Ahoj světe! €𝄞".as_bytes(),
+            b"Hello \xc3\xa9 \xe2\x82\xac \xf0\x9d\x84\x9e x",
+            b"Hello \xc3\x28 world",
+            b"\xe2\x82\xac\xe2\x82",
+            b"abc\xed\xa0\x80def",
+            b"\xf0\x90\x80",
+            b"\xff",
+        ];
+
+        let checkers: &[fn() -> Box<dyn EncodingChecker>] = &[
+            || Box::new(Ascii::new()),
+            || Box::new(Utf8::new()),
+            || Box::new(Ignore::new()),
+        ];
+
+        for checker in checkers {
+            for input in inputs {
+                for split in 0..=input.len() {
+                    let mut expected = checker();
+                    let expected_result = input
+                        .iter()
+                        .position(|byte| !expected.feed(*byte))
+                        .map_or(Ok(()), Err);
+
+                    let mut actual = checker();
+                    let (first, second) = input.split_at(split);
+                    let actual_result = actual
+                        .feed_slice(first)
+                        .and_then(|_| actual.feed_slice(second).map_err(|i| split + i));
+
+                    assert_eq!(actual_result, expected_result, "{:?} {}", input, split);
+                    assert_eq!(actual.pending(), expected.pending());
+                    assert_eq!(actual.finish(), expected.finish());
+                }
+            }
+        }
     }
 }
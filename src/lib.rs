@@ -1,15 +1,37 @@
+mod adapters;
+mod binary;
+mod codepages;
+mod converter;
+mod cow;
 mod encodings;
+mod parallel;
+mod position;
+mod report;
+mod stats;
 mod transforms;
 
 use std::fmt;
 use std::io::{self, Read, Write};
 
-pub use self::encodings::{Encoding, EncodingChecker};
+pub use self::adapters::{Reader, Writer};
+pub use self::binary::{is_binary, BINARY_CHECK_SIZE};
+pub use self::converter::Converter;
+pub use self::cow::{convert_bytes, convert_str};
+pub use self::encodings::{CodeUnit, Encoding, EncodingChecker};
+pub use self::parallel::process_slice_parallel;
+pub use self::report::Report;
+pub use self::stats::{analyze, LineEndingStats};
 pub use self::transforms::{Transform, TransformMode};
 
-const BUFFER_SIZE: usize = 4096;
+/// Default size of the buffer used for reading the input.
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
 
-/// Configuration for processing. Two things can be set: encoding of input and type of line ending.
+/// Default size of the beginning of the input which is inspected by
+/// [TransformMode::Auto](enum.TransformMode.html#variant.Auto).
+pub const DEFAULT_LOOKAHEAD: usize = 64 * 1024;
+
+/// Configuration for processing. The following things can be set: encoding of input, type of line
+/// ending, the policy for byte order mark and conversion of the input into UTF-8.
 ///
 /// ```
 /// use std::io::Cursor;
@@ -28,6 +50,10 @@ const BUFFER_SIZE: usize = 4096;
 pub struct Config<E: Into<Box<dyn EncodingChecker>>, T: Into<Box<dyn Transform>>> {
     encoding_checker: E,
     transform_mode: T,
+    bom: Bom,
+    transcode: bool,
+    buffer_size: usize,
+    lookahead: usize,
 }
 
 impl Config<Encoding, TransformMode> {
@@ -36,6 +62,10 @@ impl Config<Encoding, TransformMode> {
         Config {
             encoding_checker: Encoding::Ignore,
             transform_mode: TransformMode::Lf,
+            bom: Bom::Keep,
+            transcode: false,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            lookahead: DEFAULT_LOOKAHEAD,
         }
     }
 }
@@ -60,6 +90,72 @@ impl<E: Into<Box<dyn EncodingChecker>>, T: Into<Box<dyn Transform>>> Config<E, T
             ..self
         }
     }
+
+    /// Changes the policy for byte order mark. For more info, see documentation for
+    /// [Bom](enum.Bom.html).
+    pub fn bom(self, bom: Bom) -> Self {
+        Config { bom, ..self }
+    }
+
+    /// Enables or disables conversion of the input into UTF-8. It has effect only for single-byte
+    /// encodings (ISO-8859-1, Windows-1252 and CP437), the input in other encodings is written as
+    /// it is.
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    ///
+    /// use loe::{process, Config, Encoding};
+    ///
+    /// let mut input = Cursor::new(b"\x93caf\xe9\x94\r\n");
+    /// let mut output = Cursor::new(Vec::new());
+    ///
+    /// process(&mut input, &mut output, Config::default().encoding(Encoding::Windows1252).transcode(true));
+    /// let actual = String::from_utf8(output.into_inner()).unwrap();
+    /// assert_eq!(actual, "“café”\n");
+    /// ```
+    pub fn transcode(self, transcode: bool) -> Self {
+        Config { transcode, ..self }
+    }
+
+    /// Changes the size of the buffer used for reading the input. The output is written once per
+    /// read, so larger buffer means fewer system calls at the cost of memory. In
+    /// [process_slice](fn.process_slice.html), it is the size of chunks in which the output is
+    /// written. The default is [DEFAULT_BUFFER_SIZE](constant.DEFAULT_BUFFER_SIZE.html).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is zero.
+    pub fn buffer_size(self, buffer_size: usize) -> Self {
+        assert!(buffer_size > 0, "buffer size must be positive");
+        Config {
+            buffer_size,
+            ..self
+        }
+    }
+
+    /// Changes the size of the beginning of the input which is inspected by transforms that need
+    /// it, such as [TransformMode::Auto](enum.TransformMode.html#variant.Auto), before the
+    /// conversion starts. The beginning is held in memory until it is read entirely, so it can be
+    /// set to the size of the input to take all of it into account. The default is
+    /// [DEFAULT_LOOKAHEAD](constant.DEFAULT_LOOKAHEAD.html).
+    ///
+    /// ```
+    /// use loe::{convert_bytes, Config, TransformMode};
+    ///
+    /// let input = b"a\nb\r\nc\r\nd\r\n";
+    /// let config = Config::default().transform(TransformMode::Auto);
+    ///
+    /// assert_eq!(&*convert_bytes(input, config.clone()).unwrap(), b"a\r\nb\r\nc\r\nd\r\n");
+    /// assert_eq!(&*convert_bytes(input, config.lookahead(4)).unwrap(), b"a\nb\nc\nd\n");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is zero.
+    pub fn lookahead(self, lookahead: usize) -> Self {
+        assert!(lookahead > 0, "lookahead must be positive");
+        Config { lookahead, ..self }
+    }
 }
 
 impl Default for Config<Encoding, TransformMode> {
@@ -68,38 +164,205 @@ impl Default for Config<Encoding, TransformMode> {
     }
 }
 
+/// Policy for byte order mark (BOM) at the beginning of the input. The byte order mark is UTF-8
+/// one (`EF BB BF`) unless the encoding is UTF-16, for which it is encoded in the byte order of the
+/// input.
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use loe::{process, Bom, Config};
+///
+/// let mut input = Cursor::new("\u{feff}hello\r\n");
+/// let mut output = Cursor::new(Vec::new());
+///
+/// process(&mut input, &mut output, Config::default().bom(Bom::Remove));
+/// let actual = String::from_utf8(output.into_inner()).unwrap();
+/// assert_eq!(actual, "hello\n");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bom {
+    /// The byte order mark is kept if present and it is not added if missing.
+    Keep,
+    /// The byte order mark is removed if present.
+    Remove,
+    /// The byte order mark is added if missing.
+    Add,
+}
+
 /// Error which can occur during processing.
 #[derive(Debug)]
 pub enum ParseError {
-    /// The input is in invalid encoding. This enum variant also holds the name of expected
-    /// encoding.
-    InvalidEncoding(String),
-    /// An I/O error occurred.
+    /// The input is in invalid encoding. This enum variant holds the name of expected encoding
+    /// and the location of the first invalid byte.
+    InvalidEncoding {
+        /// Name of the expected encoding.
+        encoding: String,
+        /// Zero-based byte offset of the invalid byte.
+        offset: u64,
+        /// One-based line number of the invalid byte.
+        line: u64,
+        /// One-based column number (in bytes) of the invalid byte.
+        column: u64,
+        /// The offending byte sequence, that is, the bytes of an incomplete character (if any)
+        /// followed by the invalid byte.
+        sequence: Vec<u8>,
+    },
+    /// The input ends in the middle of a character of expected encoding. This enum variant holds
+    /// the name of expected encoding and the location of the incomplete character.
+    IncompleteEncoding {
+        /// Name of the expected encoding.
+        encoding: String,
+        /// Zero-based byte offset of the incomplete character.
+        offset: u64,
+        /// One-based line number of the incomplete character.
+        line: u64,
+        /// One-based column number (in bytes) of the incomplete character.
+        column: u64,
+        /// The bytes of the incomplete character.
+        sequence: Vec<u8>,
+    },
+    /// An I/O error occurred. Errors of reading the input and writing the output keep their kind
+    /// and the message says which of the two failed.
     IoError(io::Error),
 }
 
+impl ParseError {
+    pub(crate) fn read_error(err: io::Error) -> Self {
+        ParseError::IoError(IoContext::wrap("read the input", err))
+    }
+
+    pub(crate) fn write_error(err: io::Error) -> Self {
+        ParseError::IoError(IoContext::wrap("write the output", err))
+    }
+
+    /// Returns a copy of the error. I/O errors cannot be copied, so only their kind and message
+    /// are kept.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            ParseError::InvalidEncoding {
+                encoding,
+                offset,
+                line,
+                column,
+                sequence,
+            } => ParseError::InvalidEncoding {
+                encoding: encoding.clone(),
+                offset: *offset,
+                line: *line,
+                column: *column,
+                sequence: sequence.clone(),
+            },
+            ParseError::IncompleteEncoding {
+                encoding,
+                offset,
+                line,
+                column,
+                sequence,
+            } => ParseError::IncompleteEncoding {
+                encoding: encoding.clone(),
+                offset: *offset,
+                line: *line,
+                column: *column,
+                sequence: sequence.clone(),
+            },
+            ParseError::IoError(err) => {
+                ParseError::IoError(io::Error::new(err.kind(), err.to_string()))
+            }
+        }
+    }
+}
+
+/// I/O error with the operation during which it occurred.
+#[derive(Debug)]
+struct IoContext {
+    operation: &'static str,
+    source: io::Error,
+}
+
+impl IoContext {
+    fn wrap(operation: &'static str, source: io::Error) -> io::Error {
+        io::Error::new(source.kind(), IoContext { operation, source })
+    }
+}
+
+impl fmt::Display for IoContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to {}: {}", self.operation, self.source)
+    }
+}
+
+impl std::error::Error for IoContext {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ParseError::InvalidEncoding(ref encoding) => {
-                write!(f, "file is not in expected encoding '{}'", encoding)
+            ParseError::InvalidEncoding {
+                ref encoding,
+                line,
+                column,
+                ref sequence,
+                ..
+            } => {
+                write!(f, "{}:{}: invalid {} byte", line, column, encoding)?;
+
+                match sequence.split_last() {
+                    Some((byte, [])) => write!(f, " 0x{:02x}", byte),
+                    Some((byte, _)) => {
+                        write!(f, " 0x{:02x} (in sequence", byte)?;
+                        for byte in sequence {
+                            write!(f, " 0x{:02x}", byte)?;
+                        }
+                        write!(f, ")")
+                    }
+                    None => Ok(()),
+                }
+            }
+            ParseError::IncompleteEncoding {
+                ref encoding,
+                line,
+                column,
+                ref sequence,
+                ..
+            } => {
+                write!(f, "{}:{}: incomplete {} sequence", line, column, encoding)?;
+                for byte in sequence {
+                    write!(f, " 0x{:02x}", byte)?;
+                }
+                write!(f, " at the end of input")
             }
             ParseError::IoError(ref err) => write!(f, "{}", err),
         }
     }
 }
 
+impl From<ParseError> for io::Error {
+    /// I/O errors are unwrapped, other errors are wrapped in an error of `InvalidData` kind.
+    fn from(err: ParseError) -> Self {
+        match err {
+            ParseError::IoError(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
 impl std::error::Error for ParseError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            ParseError::InvalidEncoding(_) => None,
+            ParseError::InvalidEncoding { .. } => None,
+            ParseError::IncompleteEncoding { .. } => None,
             ParseError::IoError(error) => Some(error),
         }
     }
 }
 
 /// The entry point of *loe*. It processes the given input and write the result into the given
-/// output. Its behavior is dependent on given config.
+/// output. Its behavior is dependent on given config. The returned [Report](struct.Report.html)
+/// summarizes what was done, for example, whether the output differs from the input.
 ///
 /// # Examples
 ///
@@ -119,7 +382,8 @@ impl std::error::Error for ParseError {
 /// assert_eq!(actual, expected);
 /// ```
 ///
-/// Wrapping the function to convert string to string:
+/// Wrapping the function to convert string to string (see also
+/// [convert_str](fn.convert_str.html) which does this without unnecessary allocations):
 ///
 /// ```
 /// use std::io::Cursor;
@@ -140,39 +404,64 @@ pub fn process<I, O, E, T>(
     input: &mut I,
     output: &mut O,
     config: Config<E, T>,
-) -> Result<(), ParseError>
+) -> Result<Report, ParseError>
 where
     I: Read,
     O: Write,
     E: Into<Box<dyn EncodingChecker>> + fmt::Display,
     T: Into<Box<dyn Transform>>,
 {
-    let encoding_name = format!("{}", config.encoding_checker);
-    let mut encoding: Box<dyn EncodingChecker> = config.encoding_checker.into();
-    let mut transform: Box<dyn Transform> = config.transform_mode.into();
-
-    let mut read_buffer = [0; BUFFER_SIZE];
-    let mut write_buffer = [0; 2 * BUFFER_SIZE];
+    let mut read_buffer = vec![0; config.buffer_size];
+    let mut converter = Converter::new(config);
 
-    while let Ok(n) = input.read(&mut read_buffer) {
-        if n == 0 {
-            break;
+    loop {
+        match input.read(&mut read_buffer) {
+            Ok(0) => break,
+            Ok(n) => converter.convert(&read_buffer[0..n], output)?,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(ParseError::read_error(err)),
         }
+    }
 
-        let mut out_ptr = 0;
-        for in_ptr in 0..n {
-            if !encoding.feed(read_buffer[in_ptr]) {
-                return Err(ParseError::InvalidEncoding(encoding_name));
-            }
-            out_ptr = transform.transform_buffer(in_ptr, out_ptr, &read_buffer, &mut write_buffer);
-        }
+    converter.finish(output)
+}
+
+/// Processes the input which is already in memory, for example, a memory-mapped file. It behaves
+/// the same as [process](fn.process.html), but no copying into a read buffer is needed. The input
+/// is transformed in chunks of the buffer size set in the config so that the memory needed for the
+/// output does not grow with the input. The report is the same as from [process](fn.process.html).
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use loe::{process_slice, Config};
+///
+/// let mut output = Cursor::new(Vec::new());
+///
+/// process_slice(b"hello\r\nworld!\r\n", &mut output, Config::default());
+/// let actual = String::from_utf8(output.into_inner()).unwrap();
+/// assert_eq!(actual, "hello\nworld!\n");
+/// ```
+pub fn process_slice<O, E, T>(
+    input: &[u8],
+    output: &mut O,
+    config: Config<E, T>,
+) -> Result<Report, ParseError>
+where
+    O: Write,
+    E: Into<Box<dyn EncodingChecker>> + fmt::Display,
+    T: Into<Box<dyn Transform>>,
+{
+    let chunk_size = config.buffer_size;
+    let mut converter = Converter::new(config);
 
-        output
-            .write(&write_buffer[0..out_ptr])
-            .map_err(ParseError::IoError)?;
+    for chunk in input.chunks(chunk_size) {
+        converter.convert(chunk, output)?;
     }
 
-    Ok(())
+    converter.finish(output)
 }
 
 #[cfg(test)]
@@ -197,6 +486,444 @@ mod tests {
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn invalid_encoding() {
+        let mut input = Cursor::new(&b"hello\r\nworld\xc3!\r\n"[..]);
+        let mut output = Cursor::new(Vec::new());
+
+        match process(
+            &mut input,
+            &mut output,
+            Config::default().encoding(Encoding::Utf8),
+        ) {
+            Err(err @ ParseError::InvalidEncoding { .. }) => {
+                assert_eq!(
+                    err.to_string(),
+                    "2:7: invalid UTF-8 byte 0x21 (in sequence 0xc3 0x21)"
+                );
+                match err {
+                    ParseError::InvalidEncoding {
+                        offset, sequence, ..
+                    } => {
+                        assert_eq!(offset, 13);
+                        assert_eq!(sequence, vec![0xc3, 0x21]);
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            _ => panic!("expected invalid encoding error"),
+        }
+
+        let mut input = Cursor::new("a\nb\nčau");
+        match process(
+            &mut input,
+            &mut output,
+            Config::default().encoding(Encoding::Ascii),
+        ) {
+            Err(err) => assert_eq!(err.to_string(), "3:1: invalid Ascii byte 0xc4"),
+            _ => panic!("expected invalid encoding error"),
+        }
+    }
+
+    #[test]
+    fn incomplete_encoding() {
+        let mut input = Cursor::new(&b"hello\r\nworld \xe2\x82"[..]);
+        let mut output = Cursor::new(Vec::new());
+
+        match process(
+            &mut input,
+            &mut output,
+            Config::default().encoding(Encoding::Utf8),
+        ) {
+            Err(err @ ParseError::IncompleteEncoding { .. }) => {
+                assert_eq!(
+                    err.to_string(),
+                    "2:7: incomplete UTF-8 sequence 0xe2 0x82 at the end of input"
+                );
+                match err {
+                    ParseError::IncompleteEncoding { offset, .. } => assert_eq!(offset, 13),
+                    _ => unreachable!(),
+                }
+            }
+            _ => panic!("expected incomplete encoding error"),
+        }
+    }
+
+    #[test]
+    fn slice_invalid_encoding() {
+        let mut output = Cursor::new(Vec::new());
+        let config = Config::default().encoding(Encoding::Utf8).buffer_size(4);
+
+        match process_slice(b"hello\r\nworld\xc3!\r\n", &mut output, config) {
+            Err(err @ ParseError::InvalidEncoding { .. }) => assert_eq!(
+                err.to_string(),
+                "2:7: invalid UTF-8 byte 0x21 (in sequence 0xc3 0x21)"
+            ),
+            _ => panic!("expected invalid encoding error"),
+        }
+    }
+
+    #[test]
+    fn utf16() {
+        let input = "\u{feff}Ahoj\r\nsvěte!\r\n";
+        let expected = "\u{feff}Ahoj\nsvěte!\n";
+
+        for encoding in &[Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Utf16] {
+            let big_endian = *encoding == Encoding::Utf16Be;
+            let encode = |text: &str| -> Vec<u8> {
+                text.encode_utf16()
+                    .flat_map(|unit| {
+                        if big_endian {
+                            unit.to_be_bytes()
+                        } else {
+                            unit.to_le_bytes()
+                        }
+                    })
+                    .collect()
+            };
+
+            let mut input = Cursor::new(encode(input));
+            let mut output = Cursor::new(Vec::new());
+
+            process(
+                &mut input,
+                &mut output,
+                Config::default().encoding(*encoding),
+            )
+            .unwrap();
+            assert_eq!(output.into_inner(), encode(expected));
+        }
+    }
+
+    #[test]
+    fn utf16_error_location() {
+        for &buffer_size in &[1, 2, 64] {
+            let check = |input: &'static [u8]| {
+                let config = Config::default()
+                    .encoding(Encoding::Utf16Le)
+                    .buffer_size(buffer_size);
+                process(
+                    &mut Cursor::new(input),
+                    &mut Cursor::new(Vec::new()),
+                    config,
+                )
+                .unwrap_err()
+                .to_string()
+            };
+
+            // the bytes of the incomplete character look like line endings
+            assert_eq!(
+                check(b"A\0\x0a\xd8\x0d"),
+                "1:3: incomplete UTF-16LE sequence 0x0a 0xd8 0x0d at the end of input"
+            );
+            // U+0D0A is not a line ending
+            assert_eq!(
+                check(b"A\0\x0a\x0d\0\xdc"),
+                "1:6: invalid UTF-16LE byte 0xdc (in sequence 0x00 0xdc)"
+            );
+        }
+    }
+
+    /// Reader which returns given results one by one and then the end of the input.
+    struct MockReader {
+        results: Vec<io::Result<&'static [u8]>>,
+    }
+
+    impl Read for MockReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.results.is_empty() {
+                return Ok(0);
+            }
+
+            let data = self.results.remove(0)?;
+            buf[0..data.len()].copy_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    /// Writer which accepts at most `chunk` bytes at a time, fails with `Interrupted` every other
+    /// call and fails with given error after `limit` bytes were written.
+    struct MockWriter {
+        written: Vec<u8>,
+        chunk: usize,
+        limit: usize,
+        error: Option<io::ErrorKind>,
+        interrupt: bool,
+    }
+
+    impl MockWriter {
+        fn new(chunk: usize) -> Self {
+            MockWriter {
+                written: Vec::new(),
+                chunk,
+                limit: usize::MAX,
+                error: None,
+                interrupt: false,
+            }
+        }
+
+        fn failing(limit: usize, error: io::ErrorKind) -> Self {
+            MockWriter {
+                limit,
+                error: Some(error),
+                ..MockWriter::new(usize::MAX)
+            }
+        }
+    }
+
+    impl Write for MockWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+
+            let len = buf
+                .len()
+                .min(self.chunk)
+                .min(self.limit - self.written.len());
+
+            if len == 0 && !buf.is_empty() {
+                return match self.error {
+                    Some(kind) => Err(io::Error::new(kind, "mock failure")),
+                    None => Ok(0),
+                };
+            }
+
+            self.written.extend_from_slice(&buf[0..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_error() {
+        let mut input = MockReader {
+            results: vec![
+                Ok(b"hello\r\n"),
+                Err(io::Error::other("mock failure")),
+                Ok(b"world!\r\n"),
+            ],
+        };
+        let mut output = Vec::new();
+
+        match process(&mut input, &mut output, Config::default()) {
+            Err(ParseError::IoError(err)) => {
+                assert_eq!(err.kind(), io::ErrorKind::Other);
+                assert_eq!(err.to_string(), "failed to read the input: mock failure");
+            }
+            _ => panic!("expected I/O error"),
+        }
+
+        assert_eq!(output, b"hello\n");
+    }
+
+    #[test]
+    fn read_interrupted() {
+        let mut input = MockReader {
+            results: vec![
+                Err(io::ErrorKind::Interrupted.into()),
+                Ok(b"hello\r"),
+                Err(io::ErrorKind::Interrupted.into()),
+                Ok(b"\nworld!\r\n"),
+            ],
+        };
+        let mut output = Vec::new();
+
+        process(&mut input, &mut output, Config::default()).unwrap();
+        assert_eq!(output, b"hello\nworld!\n");
+    }
+
+    #[test]
+    fn short_writes() {
+        let mut input = Cursor::new("hello\r\nworld!\r\n");
+        let mut output = MockWriter::new(3);
+
+        process(&mut input, &mut output, Config::default().bom(Bom::Add)).unwrap();
+        assert_eq!(output.written, b"\xef\xbb\xbfhello\nworld!\n");
+    }
+
+    #[test]
+    fn write_error() {
+        let mut input = Cursor::new("hello\r\nworld!\r\n");
+        let mut output = MockWriter::failing(4, io::ErrorKind::PermissionDenied);
+
+        match process(&mut input, &mut output, Config::default()) {
+            Err(ParseError::IoError(err)) => {
+                assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+                assert_eq!(err.to_string(), "failed to write the output: mock failure");
+            }
+            _ => panic!("expected I/O error"),
+        }
+
+        // a writer which does not accept anything
+        let mut input = Cursor::new("hello");
+        let mut output = MockWriter::new(0);
+
+        match process(&mut input, &mut output, Config::default()) {
+            Err(ParseError::IoError(err)) => assert_eq!(err.kind(), io::ErrorKind::WriteZero),
+            _ => panic!("expected I/O error"),
+        }
+    }
+
+    #[test]
+    fn write_error_slice() {
+        let mut output = MockWriter::failing(0, io::ErrorKind::Other);
+        let config = Config::default().buffer_size(2);
+
+        match process_slice(b"hello\r\n", &mut output, config) {
+            Err(ParseError::IoError(err)) => {
+                assert_eq!(err.to_string(), "failed to write the output: mock failure")
+            }
+            _ => panic!("expected I/O error"),
+        }
+    }
+
+    fn process_bom(input: &[u8], encoding: Encoding, bom: Bom) -> Vec<u8> {
+        let mut input = Cursor::new(input);
+        let mut output = Cursor::new(Vec::new());
+        let config = Config::default().encoding(encoding).bom(bom);
+
+        process(&mut input, &mut output, config).unwrap();
+        output.into_inner()
+    }
+
+    #[test]
+    fn bom() {
+        let with = b"\xef\xbb\xbfa\r\n";
+        let without = b"a\r\n";
+
+        for encoding in &[Encoding::Ignore, Encoding::Utf8] {
+            assert_eq!(process_bom(with, *encoding, Bom::Keep), b"\xef\xbb\xbfa\n");
+            assert_eq!(process_bom(with, *encoding, Bom::Remove), b"a\n");
+            assert_eq!(process_bom(with, *encoding, Bom::Add), b"\xef\xbb\xbfa\n");
+            assert_eq!(process_bom(without, *encoding, Bom::Keep), b"a\n");
+            assert_eq!(process_bom(without, *encoding, Bom::Remove), b"a\n");
+            assert_eq!(
+                process_bom(without, *encoding, Bom::Add),
+                b"\xef\xbb\xbfa\n"
+            );
+        }
+
+        // prefix of the byte order mark only
+        assert_eq!(
+            process_bom(b"\xef\xbb", Encoding::Ignore, Bom::Remove),
+            b"\xef\xbb"
+        );
+        assert_eq!(process_bom(b"", Encoding::Ignore, Bom::Remove), b"");
+    }
+
+    #[test]
+    fn bom_split() {
+        let mut output = Cursor::new(Vec::new());
+        let mut converter = Converter::new(Config::default().bom(Bom::Remove));
+
+        for byte in b"\xef\xbb\xbfa\r\n" {
+            converter.convert(&[*byte], &mut output).unwrap();
+        }
+
+        converter.finish(&mut output).unwrap();
+        assert_eq!(output.into_inner(), b"a\n");
+    }
+
+    #[test]
+    fn bom_utf16() {
+        let le = b"\xff\xfea\x00\r\x00\n\x00";
+        let be = b"\xfe\xff\x00a\x00\r\x00\n";
+
+        assert_eq!(
+            process_bom(le, Encoding::Utf16, Bom::Remove),
+            b"a\x00\n\x00"
+        );
+        assert_eq!(
+            process_bom(be, Encoding::Utf16, Bom::Remove),
+            b"\x00a\x00\n"
+        );
+        assert_eq!(
+            process_bom(be, Encoding::Utf16Be, Bom::Keep),
+            b"\xfe\xff\x00a\x00\n"
+        );
+        assert_eq!(
+            process_bom(b"\x00a\x00\n", Encoding::Utf16Be, Bom::Add),
+            b"\xfe\xff\x00a\x00\n"
+        );
+        assert_eq!(
+            process_bom(b"a\x00\n\x00", Encoding::Utf16, Bom::Add),
+            b"\xff\xfea\x00\n\x00"
+        );
+    }
+
+    #[test]
+    fn transcode() {
+        let transcode = |input: &[u8], encoding: Encoding| {
+            let mut input = Cursor::new(input);
+            let mut output = Cursor::new(Vec::new());
+            let config = Config::default()
+                .encoding(encoding)
+                .transform(TransformMode::Lf)
+                .transcode(true);
+
+            process(&mut input, &mut output, config).unwrap();
+            String::from_utf8(output.into_inner()).unwrap()
+        };
+
+        assert_eq!(transcode(b"Gr\xfc\xdfe\r\n", Encoding::Latin1), "Grüße\n");
+        assert_eq!(
+            transcode(b"\x80 \x85\r\n\xa0", Encoding::Windows1252),
+            "€ …\n\u{a0}"
+        );
+        assert_eq!(
+            transcode(b"\xc9\xcd\xbb\r\n\xe3", Encoding::Cp437),
+            "╔═╗\nπ"
+        );
+
+        // other encodings are not affected
+        assert_eq!(
+            transcode("Ahoj světe!\r\n".as_bytes(), Encoding::Utf8),
+            "Ahoj světe!\n"
+        );
+
+        // the byte order mark is added in UTF-8
+        let mut input = Cursor::new(&b"\xe9"[..]);
+        let mut output = Cursor::new(Vec::new());
+        let config = Config::default()
+            .encoding(Encoding::Latin1)
+            .transcode(true)
+            .bom(Bom::Add);
+        process(&mut input, &mut output, config).unwrap();
+        assert_eq!(output.into_inner(), "\u{feff}é".as_bytes());
+    }
+
+    #[test]
+    fn auto() {
+        let auto = |input: &[u8], lookahead: usize| {
+            let mut output = Cursor::new(Vec::new());
+            let config = Config::default()
+                .transform(TransformMode::Auto)
+                .buffer_size(2)
+                .lookahead(lookahead);
+
+            let report = process(&mut Cursor::new(input), &mut output, config).unwrap();
+            (output.into_inner(), report.converted())
+        };
+
+        let input = b"a\nb\r\nc\r\nd\re\r\n";
+        assert_eq!(auto(input, 100), (b"a\r\nb\r\nc\r\nd\r\ne\r\n".to_vec(), 2));
+        assert_eq!(auto(input, 3), (b"a\nb\nc\nd\ne\n".to_vec(), 4));
+        assert_eq!(auto(b"", 3), (Vec::new(), 0));
+
+        // the byte order mark is still handled
+        let mut output = Cursor::new(Vec::new());
+        let config = Config::default()
+            .transform(TransformMode::Auto)
+            .bom(Bom::Remove);
+        process(&mut Cursor::new(b"\xef\xbb\xbfa\rb\r"), &mut output, config).unwrap();
+        assert_eq!(output.into_inner(), b"a\rb\r");
+    }
+
     fn filter(iterator: impl Iterator<Item = u8>) -> Vec<u8> {
         iterator
             .filter(|b| b != &LF_BYTE && b != &CR_BYTE)
@@ -226,6 +953,25 @@ mod tests {
 
             let output = output.into_inner();
 
+            // every LF byte is preceded by CR byte and every CR byte is followed by LF byte
+            for (i, b) in output.iter().enumerate() {
+                if b == &LF_BYTE {
+                    prop_assert!(i > 0 && output[i - 1] == CR_BYTE, "no lone LF byte");
+                } else if b == &CR_BYTE {
+                    prop_assert!(output.get(i + 1) == Some(&LF_BYTE), "no lone CR byte");
+                }
+            }
+        }
+
+        #[test]
+        fn prop_cr(data in ".*") {
+            let mut input = Cursor::new(data);
+            let mut output = Cursor::new(Vec::<u8>::new());
+
+            process(&mut input, &mut output, Config::default().transform(TransformMode::Cr)).unwrap();
+
+            let output = output.into_inner();
+
             // no LF byte
             prop_assert!(!output.iter().any(|b| b == &LF_BYTE), "no LF byte");
         }
@@ -247,6 +993,76 @@ mod tests {
             prop_assert!(input == output, "CRLF to LF and back");
         }
 
+        #[test]
+        fn prop_inverse_cr(data in "[^\\r]*") {
+            let mut input = Cursor::new(data);
+            let mut output = Cursor::new(Vec::<u8>::new());
+            let mut output2 = Cursor::new(Vec::<u8>::new());
+
+            process(&mut input, &mut output, Config::default().transform(TransformMode::Cr)).unwrap();
+
+            output.set_position(0);
+            process(&mut output, &mut output2, Config::default().transform(TransformMode::Lf)).unwrap();
+
+            let input = input.into_inner().bytes().collect::<Vec<_>>();
+            let output = output2.into_inner();
+
+            prop_assert!(input == output, "CR to LF and back");
+        }
+
+
+        #[test]
+        fn prop_buffer_size(data in "(\\r|\\n|\u{feff}|.)*", buffer_size in 1usize..64) {
+            for transform in &[TransformMode::Lf, TransformMode::Crlf, TransformMode::Cr] {
+                for bom in &[Bom::Keep, Bom::Remove, Bom::Add] {
+                    let config = || Config::default()
+                        .encoding(Encoding::Utf8)
+                        .transform(*transform)
+                        .bom(*bom);
+
+                    let mut input = Cursor::new(data.as_bytes());
+                    let mut expected = Cursor::new(Vec::<u8>::new());
+                    process(&mut input, &mut expected, config()).unwrap();
+
+                    let mut input = Cursor::new(data.as_bytes());
+                    let mut output = Cursor::new(Vec::<u8>::new());
+                    process(&mut input, &mut output, config().buffer_size(buffer_size)).unwrap();
+
+                    prop_assert!(output.into_inner() == expected.into_inner(), "same output for all buffer sizes");
+                }
+            }
+        }
+
+        #[test]
+        fn prop_buffer_size_utf16(data in "(\\r|\\n|.)*", buffer_size in 1usize..16) {
+            let input = data.encode_utf16().flat_map(|unit| unit.to_be_bytes()).collect::<Vec<_>>();
+
+            for transform in &[TransformMode::Lf, TransformMode::Crlf, TransformMode::Cr] {
+                let config = || Config::default().encoding(Encoding::Utf16Be).transform(*transform);
+
+                let mut expected = Cursor::new(Vec::<u8>::new());
+                process(&mut Cursor::new(&input), &mut expected, config()).unwrap();
+
+                let mut output = Cursor::new(Vec::<u8>::new());
+                process(&mut Cursor::new(&input), &mut output, config().buffer_size(buffer_size)).unwrap();
+
+                prop_assert!(output.into_inner() == expected.into_inner(), "same output for all buffer sizes");
+            }
+        }
+
+        #[test]
+        fn prop_slice(data in "(\\r|\\n|\u{feff}|.)*", buffer_size in 1usize..64) {
+            let config = || Config::default().encoding(Encoding::Utf8).bom(Bom::Remove);
+
+            let mut input = Cursor::new(data.as_bytes());
+            let mut expected = Cursor::new(Vec::<u8>::new());
+            process(&mut input, &mut expected, config()).unwrap();
+
+            let mut output = Cursor::new(Vec::<u8>::new());
+            process_slice(data.as_bytes(), &mut output, config().buffer_size(buffer_size)).unwrap();
+
+            prop_assert!(output.into_inner() == expected.into_inner(), "same output as from reader");
+        }
 
         #[test]
         fn prop_preserve_rest(data in ".*") {
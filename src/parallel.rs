@@ -0,0 +1,328 @@
+//! This module provides parallel processing of large inputs which are in memory. The input is split
+//! into chunks which are converted on multiple threads and written to the output in order.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::Cursor;
+//!
+//! use loe::{process_slice_parallel, Config};
+//!
+//! let input = "hello\r\nworld!\r\n".repeat(100_000);
+//! let mut output = Cursor::new(Vec::new());
+//!
+//! process_slice_parallel(input.as_bytes(), &mut output, Config::default(), 4).unwrap();
+//! let actual = String::from_utf8(output.into_inner()).unwrap();
+//! assert_eq!(actual, "hello\nworld!\n".repeat(100_000));
+//! ```
+
+use std::fmt;
+use std::io::Write;
+use std::ops::Range;
+use std::panic;
+use std::thread;
+
+use crate::converter::Converter;
+use crate::position::Position;
+use crate::transforms::{find_line_ending, CR_CHAR, LF_CHAR};
+use crate::{process_slice, CodeUnit, Config, EncodingChecker, ParseError, Report, Transform};
+
+/// Approximate size of chunks which are converted on a single thread.
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Processes the input which is already in memory using given number of threads. The result is
+/// the same as from [process_slice](fn.process_slice.html), including the report.
+///
+/// The input is split into chunks right behind line endings, so no CRLF pair or a multi-byte
+/// character is divided. The chunks are converted in rounds of `jobs` chunks at a time, so the
+/// memory needed for the output is bounded. If there are no line endings, the input cannot be
+/// split. UTF-16 input, small inputs and transforms which look ahead (such as
+/// [TransformMode::Auto](enum.TransformMode.html#variant.Auto)) are always processed on the current
+/// thread.
+///
+/// If the input is in invalid encoding, the converted chunks preceding the one with the error are
+/// written to the output and the error is returned with the location relative to the whole input.
+pub fn process_slice_parallel<O, E, T>(
+    input: &[u8],
+    output: &mut O,
+    config: Config<E, T>,
+    jobs: usize,
+) -> Result<Report, ParseError>
+where
+    O: Write,
+    E: Into<Box<dyn EncodingChecker>> + fmt::Display + Clone + Send,
+    T: Into<Box<dyn Transform>> + Clone + Send,
+{
+    process_chunks(input, output, config, jobs, CHUNK_SIZE)
+}
+
+fn process_chunks<O, E, T>(
+    input: &[u8],
+    output: &mut O,
+    config: Config<E, T>,
+    jobs: usize,
+    chunk_size: usize,
+) -> Result<Report, ParseError>
+where
+    O: Write,
+    E: Into<Box<dyn EncodingChecker>> + fmt::Display + Clone + Send,
+    T: Into<Box<dyn Transform>> + Clone + Send,
+{
+    let checker: Box<dyn EncodingChecker> = config.encoding_checker.clone().into();
+    let transform: Box<dyn Transform> = config.transform_mode.clone().into();
+
+    // code units of UTF-16 cannot be recognized in an arbitrary place and the transforms which
+    // look ahead must see the beginning of the whole input
+    if jobs <= 1
+        || checker.code_unit() != CodeUnit::Byte
+        || transform.needs_lookahead()
+        || input.len() <= chunk_size
+    {
+        return process_slice(input, output, config);
+    }
+
+    let chunks = split(input, chunk_size);
+    let mut report: Option<Report> = None;
+
+    for round in chunks.chunks(jobs) {
+        let results = thread::scope(|scope| {
+            let handles = round
+                .iter()
+                .map(|range| {
+                    let config = config.clone();
+                    let first = range.start == 0;
+                    scope.spawn(move || convert_chunk(&input[range.clone()], config, first))
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|err| panic::resume_unwind(err))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        for (range, result) in round.iter().zip(results) {
+            match result {
+                Ok((converted, chunk_report)) => {
+                    output
+                        .write_all(&converted)
+                        .map_err(ParseError::write_error)?;
+
+                    match report {
+                        Some(ref mut report) => report.merge(&chunk_report),
+                        None => report = Some(chunk_report),
+                    }
+                }
+                Err(err) => return Err(relocate(err, &input[0..range.start])),
+            }
+        }
+    }
+
+    // the input is not empty, so there is at least one chunk
+    Ok(report.unwrap())
+}
+
+/// Converts a single chunk into a new buffer and returns it together with the report. The byte
+/// order mark is handled only in the first chunk.
+fn convert_chunk<E, T>(
+    input: &[u8],
+    config: Config<E, T>,
+    first: bool,
+) -> Result<(Vec<u8>, Report), ParseError>
+where
+    E: Into<Box<dyn EncodingChecker>> + fmt::Display,
+    T: Into<Box<dyn Transform>>,
+{
+    let mut converter = Converter::new(config);
+    if !first {
+        converter.skip_head();
+    }
+
+    let mut output = Vec::with_capacity(input.len());
+    converter.convert(input, &mut output)?;
+    let report = converter.finish(&mut output)?;
+    Ok((output, report))
+}
+
+/// Splits the input into chunks of at least given size (except the last one) which end right
+/// behind a line ending.
+fn split(input: &[u8], chunk_size: usize) -> Vec<Range<usize>> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < input.len() {
+        let end = if input.len() - start <= chunk_size {
+            input.len()
+        } else {
+            chunk_end(input, start + chunk_size)
+        };
+
+        chunks.push(start..end);
+        start = end;
+    }
+
+    chunks
+}
+
+/// Returns the index behind the first line ending which ends at or after given index.
+fn chunk_end(input: &[u8], from: usize) -> usize {
+    match find_line_ending(&input[from..]) {
+        Some(pos) => {
+            let end = from + pos;
+            if input[end] == CR_CHAR && input.get(end + 1) == Some(&LF_CHAR) {
+                end + 2
+            } else {
+                end + 1
+            }
+        }
+        None => input.len(),
+    }
+}
+
+/// Makes the location of an error in a chunk relative to the whole input. The chunk starts on a new
+/// line, so the column does not change.
+fn relocate(err: ParseError, preceding: &[u8]) -> ParseError {
+    let mut position = Position::new();
+    position.advance(preceding);
+
+    match err {
+        ParseError::InvalidEncoding {
+            encoding,
+            offset,
+            line,
+            column,
+            sequence,
+        } => ParseError::InvalidEncoding {
+            encoding,
+            offset: position.offset() + offset,
+            line: position.line() + line - 1,
+            column,
+            sequence,
+        },
+        ParseError::IncompleteEncoding {
+            encoding,
+            offset,
+            line,
+            column,
+            sequence,
+        } => ParseError::IncompleteEncoding {
+            encoding,
+            offset: position.offset() + offset,
+            line: position.line() + line - 1,
+            column,
+            sequence,
+        },
+        err => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bom, Encoding, TransformMode};
+    use proptest::{prop_assert, proptest, proptest_helper};
+    use std::io::Cursor;
+
+    #[test]
+    fn split_behind_line_endings() {
+        let input = b"ab\r\ncd\ref\n\ngh";
+        let chunks = split(input, 1)
+            .into_iter()
+            .map(|range| &input[range])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            vec![&b"ab\r\n"[..], &b"cd\r"[..], &b"ef\n"[..], &b"\ngh"[..]]
+        );
+
+        // CRLF pair at the nominal boundary
+        assert_eq!(split(b"a\r\nb", 2), vec![0..3, 3..4]);
+        assert_eq!(split(b"a\r\nb", 1), vec![0..3, 3..4]);
+        assert_eq!(split(b"abcdef", 2), vec![0..6]);
+        assert_eq!(split(b"", 2), vec![]);
+    }
+
+    fn convert(input: &[u8], config: Config<Encoding, TransformMode>, jobs: usize) -> Vec<u8> {
+        let mut output = Cursor::new(Vec::new());
+        process_chunks(input, &mut output, config, jobs, 4).unwrap();
+        output.into_inner()
+    }
+
+    #[test]
+    fn same_as_sequential() {
+        let input = "\u{feff}Ahoj\r\nsvěte!\r\rnový\nřádek\n\r\n€€€€€€€€\r\n".as_bytes();
+
+        for transform in &[TransformMode::Lf, TransformMode::Crlf, TransformMode::Cr] {
+            for bom in &[Bom::Keep, Bom::Remove, Bom::Add] {
+                let config = Config::default()
+                    .encoding(Encoding::Utf8)
+                    .transform(*transform)
+                    .bom(*bom);
+
+                let mut expected = Cursor::new(Vec::new());
+                process_slice(input, &mut expected, config.clone()).unwrap();
+                let expected = expected.into_inner();
+
+                for jobs in 1..5 {
+                    assert_eq!(convert(input, config.clone(), jobs), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn utf16_sequential() {
+        let input = "a\r\nb\r\nc\r\nd\r\n"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect::<Vec<_>>();
+        let expected = "a\nb\nc\nd\n"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let config = Config::default().encoding(Encoding::Utf16Le);
+        assert_eq!(convert(&input, config, 4), expected);
+    }
+
+    #[test]
+    fn error_location() {
+        let input = b"first\r\nsecond\r\nthird\nfou\xffrth\n";
+        let mut output = Cursor::new(Vec::new());
+        let config = Config::default().encoding(Encoding::Ascii);
+
+        match process_chunks(input, &mut output, config, 2, 4) {
+            Err(err @ ParseError::InvalidEncoding { .. }) => {
+                assert_eq!(err.to_string(), "4:4: invalid Ascii byte 0xff");
+                match err {
+                    ParseError::InvalidEncoding { offset, .. } => assert_eq!(offset, 24),
+                    _ => unreachable!(),
+                }
+            }
+            _ => panic!("expected invalid encoding error"),
+        }
+
+        // the chunks preceding the error are written
+        assert_eq!(output.into_inner(), b"first\nsecond\nthird\n");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parallel(data in "(\\r|\\n|.)*", chunk_size in 1usize..16, jobs in 1usize..5) {
+            let config = || Config::default().encoding(Encoding::Utf8).transform(TransformMode::Crlf);
+
+            let mut expected = Cursor::new(Vec::<u8>::new());
+            let expected_report = process_slice(data.as_bytes(), &mut expected, config()).unwrap();
+
+            let mut output = Cursor::new(Vec::<u8>::new());
+            let report = process_chunks(data.as_bytes(), &mut output, config(), jobs, chunk_size).unwrap();
+
+            prop_assert!(output.into_inner() == expected.into_inner(), "same output as sequential");
+            prop_assert!(report == expected_report, "same report as sequential");
+        }
+    }
+}
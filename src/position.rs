@@ -0,0 +1,147 @@
+use crate::transforms::{find_line_ending, Utf16Narrower, CR_CHAR, LF_CHAR};
+use crate::CodeUnit;
+
+/// Tracks the position in the input for error reporting. All of CRLF pair, lone LF and lone CR
+/// are recognized as a line ending. In UTF-16 input, line endings are recognized as whole code
+/// units, but the offset and column are still counted in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Position {
+    offset: u64,
+    line: u64,
+    line_start: u64,
+    after_cr: bool,
+    /// Reader of code units if the input is not byte-oriented.
+    units: Option<Utf16Narrower>,
+}
+
+impl Position {
+    pub fn new() -> Self {
+        Position::default()
+    }
+
+    /// Creates the position for input consisting of given code units.
+    pub fn with_code_unit(code_unit: CodeUnit) -> Self {
+        Position {
+            units: match code_unit {
+                CodeUnit::Byte => None,
+                _ => Some(Utf16Narrower::new(code_unit)),
+            },
+            ..Position::default()
+        }
+    }
+
+    /// Moves the position behind given bytes.
+    pub fn advance(&mut self, bytes: &[u8]) {
+        if let Some(mut units) = self.units.take() {
+            for &byte in bytes {
+                self.offset += 1;
+                if let Some((narrow, _)) = units.push(byte) {
+                    self.advance_unit(narrow);
+                }
+            }
+
+            self.units = Some(units);
+            return;
+        }
+
+        let mut start = 0;
+        while let Some(pos) = find_line_ending(&bytes[start..]) {
+            let i = start + pos;
+            let after_cr = if i == 0 {
+                self.after_cr
+            } else {
+                bytes[i - 1] == CR_CHAR
+            };
+
+            if bytes[i] == CR_CHAR || !after_cr {
+                self.line += 1;
+            }
+
+            self.line_start = self.offset + i as u64 + 1;
+            start = i + 1;
+        }
+
+        if let Some(&last) = bytes.last() {
+            self.after_cr = last == CR_CHAR;
+        }
+
+        self.offset += bytes.len() as u64;
+    }
+
+    /// Moves the position behind a narrowed code unit which ends at the current offset.
+    fn advance_unit(&mut self, narrow: u8) {
+        if narrow == LF_CHAR || narrow == CR_CHAR {
+            if narrow == CR_CHAR || !self.after_cr {
+                self.line += 1;
+            }
+
+            self.line_start = self.offset;
+        }
+
+        self.after_cr = narrow == CR_CHAR;
+    }
+
+    /// Zero-based byte offset.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// One-based line number.
+    pub fn line(&self) -> u64 {
+        self.line + 1
+    }
+
+    /// One-based column number, counted in bytes.
+    pub fn column(&self) -> u64 {
+        self.offset - self.line_start + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(bytes: &[u8]) -> (u64, u64, u64) {
+        let mut position = Position::new();
+        position.advance(bytes);
+        (position.offset(), position.line(), position.column())
+    }
+
+    #[test]
+    fn basic() {
+        assert_eq!(position(b""), (0, 1, 1));
+        assert_eq!(position(b"abc"), (3, 1, 4));
+        assert_eq!(position(b"abc\nde"), (6, 2, 3));
+        assert_eq!(position(b"a\r\nb\rc\n\nde"), (10, 5, 3));
+    }
+
+    #[test]
+    fn split() {
+        let mut position = Position::new();
+        position.advance(b"a\r");
+        assert_eq!((position.line(), position.column()), (2, 1));
+        position.advance(b"");
+        position.advance(b"\nb");
+        assert_eq!((position.line(), position.column()), (2, 2));
+    }
+
+    #[test]
+    fn utf16() {
+        let mut position = Position::with_code_unit(CodeUnit::Utf16Le);
+        // U+0D0A is not a line ending
+        position.advance(b"A\0\x0a\x0d\0");
+        assert_eq!(
+            (position.offset(), position.line(), position.column()),
+            (5, 1, 6)
+        );
+
+        let mut position = Position::with_code_unit(CodeUnit::Utf16Be);
+        position.advance(b"\0\r");
+        position.advance(b"\0");
+        position.advance(b"\n\0b");
+        assert_eq!(
+            (position.offset(), position.line(), position.column()),
+            (6, 2, 3)
+        );
+    }
+}
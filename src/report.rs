@@ -0,0 +1,174 @@
+//! This module provides the report of processing which summarizes what the conversion did.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::Cursor;
+//!
+//! use loe::{process, Config};
+//!
+//! let mut input = Cursor::new("hello\r\nworld!\r\nbye\n");
+//! let mut output = Cursor::new(Vec::new());
+//!
+//! let report = process(&mut input, &mut output, Config::default()).unwrap();
+//! assert_eq!(report.bytes_read(), 19);
+//! assert_eq!(report.bytes_written(), 17);
+//! assert_eq!(report.lines(), 3);
+//! assert_eq!(report.converted_crlf(), 2);
+//! assert_eq!(report.converted(), 2);
+//! assert!(report.is_changed());
+//! ```
+
+use crate::{LineEndingStats, TransformMode};
+
+/// Report of processing of a single input.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub(crate) bytes_read: u64,
+    pub(crate) bytes_written: u64,
+    pub(crate) line_endings: LineEndingStats,
+    pub(crate) target: Option<TransformMode>,
+    pub(crate) changed: bool,
+}
+
+impl Report {
+    /// Returns the number of bytes read from the input.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns the number of bytes written into the output.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Returns the statistics of line endings in the input.
+    pub fn line_endings(&self) -> &LineEndingStats {
+        &self.line_endings
+    }
+
+    /// Returns the number of CRLF line endings which were replaced by another line ending.
+    pub fn converted_crlf(&self) -> u64 {
+        self.converted_from(TransformMode::Crlf, self.line_endings.crlf())
+    }
+
+    /// Returns the number of LF line endings which were replaced by another line ending.
+    pub fn converted_lf(&self) -> u64 {
+        self.converted_from(TransformMode::Lf, self.line_endings.lf())
+    }
+
+    /// Returns the number of CR line endings which were replaced by another line ending.
+    pub fn converted_cr(&self) -> u64 {
+        self.converted_from(TransformMode::Cr, self.line_endings.cr())
+    }
+
+    /// Returns the total number of line endings which were replaced by another line ending. It is
+    /// always zero for custom transforms, because the line ending they write is not known.
+    pub fn converted(&self) -> u64 {
+        self.converted_crlf() + self.converted_lf() + self.converted_cr()
+    }
+
+    /// Returns the number of lines in the input. The last line is counted even if it does not end
+    /// with a line ending.
+    pub fn lines(&self) -> u64 {
+        let total = self.line_endings.total();
+        if self.bytes_read == 0 || self.line_endings.ends_with_newline() {
+            total
+        } else {
+            total + 1
+        }
+    }
+
+    /// Returns true if the output differs from the input. For custom transforms, it may be true
+    /// even if the output is the same in the end.
+    pub fn is_changed(&self) -> bool {
+        self.changed || self.converted() > 0
+    }
+
+    /// Adds the report of the input which directly follows. The input must not start with LF if
+    /// this one ends with CR.
+    pub(crate) fn merge(&mut self, next: &Report) {
+        self.bytes_read += next.bytes_read;
+        self.bytes_written += next.bytes_written;
+        self.line_endings.merge(&next.line_endings);
+        self.changed |= next.changed;
+    }
+
+    fn converted_from(&self, mode: TransformMode, count: u64) -> u64 {
+        match self.target {
+            Some(target) if target != mode => count,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_slice, Bom, Config, Encoding};
+
+    fn report(input: &[u8], config: Config<Encoding, TransformMode>) -> Report {
+        process_slice(input, &mut Vec::new(), config).unwrap()
+    }
+
+    #[test]
+    fn counts() {
+        let config = Config::default().transform(TransformMode::Crlf);
+        let report = report(b"a\r\nb\nc\rd", config);
+        assert_eq!(report.bytes_read(), 8);
+        assert_eq!(report.bytes_written(), 10);
+        assert_eq!(report.line_endings().total(), 3);
+        assert_eq!(report.converted_crlf(), 0);
+        assert_eq!(report.converted_lf(), 1);
+        assert_eq!(report.converted_cr(), 1);
+        assert_eq!(report.converted(), 2);
+        assert_eq!(report.lines(), 4);
+        assert!(report.is_changed());
+    }
+
+    #[test]
+    fn unchanged() {
+        let unchanged = report(b"hello\nworld!\n", Config::default());
+        assert_eq!(unchanged.lines(), 2);
+        assert_eq!(unchanged.converted(), 0);
+        assert!(!unchanged.is_changed());
+
+        let empty = report(b"", Config::default());
+        assert_eq!(empty.lines(), 0);
+        assert!(!empty.is_changed());
+    }
+
+    #[test]
+    fn bom() {
+        let config = Config::default().bom(Bom::Remove);
+        assert!(report(b"\xef\xbb\xbfhello\n", config.clone()).is_changed());
+        assert!(!report(b"hello\n", config).is_changed());
+
+        let config = Config::default().bom(Bom::Add);
+        let added = report(b"hello\n", config);
+        assert_eq!(added.bytes_written(), 9);
+        assert!(added.is_changed());
+    }
+
+    #[test]
+    fn transcode() {
+        let config = Config::default().encoding(Encoding::Latin1).transcode(true);
+        assert!(!report(b"hello\n", config.clone()).is_changed());
+        assert!(report(b"caf\xe9\n", config).is_changed());
+    }
+
+    #[test]
+    fn utf16() {
+        let input = "a\r\nb\nc"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_be_bytes())
+            .collect::<Vec<_>>();
+        let config = Config::default().encoding(Encoding::Utf16Be);
+        let report = report(&input, config);
+        assert_eq!(report.line_endings().crlf(), 1);
+        assert_eq!(report.line_endings().lf(), 1);
+        assert_eq!(report.converted(), 1);
+        assert_eq!(report.lines(), 3);
+        assert_eq!(report.bytes_written(), input.len() as u64 - 2);
+    }
+}
@@ -0,0 +1,218 @@
+//! This module provides line endings analysis which can be used to inspect the input without
+//! converting it.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::Cursor;
+//!
+//! use loe::{analyze, TransformMode};
+//!
+//! let mut input = Cursor::new("hello\r\nworld!\n\r\n");
+//! let stats = analyze(&mut input).unwrap();
+//!
+//! assert_eq!(stats.crlf(), 2);
+//! assert_eq!(stats.lf(), 1);
+//! assert!(stats.is_mixed());
+//! assert_eq!(stats.dominant(), Some(TransformMode::Crlf));
+//! assert!(stats.ends_with_newline());
+//! ```
+
+use std::io::{ErrorKind, Read};
+
+use crate::transforms::{find_line_ending, CR_CHAR, LF_CHAR};
+use crate::{ParseError, TransformMode, DEFAULT_BUFFER_SIZE};
+
+/// Statistics of line endings used in the input.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct LineEndingStats {
+    crlf: u64,
+    lf: u64,
+    cr: u64,
+    last: Option<u8>,
+}
+
+impl LineEndingStats {
+    /// Creates empty statistics.
+    pub fn new() -> Self {
+        LineEndingStats::default()
+    }
+
+    /// Updates the statistics with next chunk of the input. The chunks can be split at arbitrary
+    /// positions, CRLF pairs spanning two chunks are recognized correctly.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let mut start = 0;
+        while let Some(pos) = find_line_ending(&bytes[start..]) {
+            let i = start + pos;
+            let previous = if i == 0 {
+                self.last
+            } else {
+                Some(bytes[i - 1])
+            };
+
+            if bytes[i] == CR_CHAR {
+                self.cr += 1;
+            } else if previous == Some(CR_CHAR) {
+                // the CR was counted as a lone one
+                self.cr -= 1;
+                self.crlf += 1;
+            } else {
+                self.lf += 1;
+            }
+
+            start = i + 1;
+        }
+
+        if let Some(&last) = bytes.last() {
+            self.last = Some(last);
+        }
+    }
+
+    /// Adds the statistics of the input which directly follows. The input must not start with LF
+    /// if this one ends with CR.
+    pub(crate) fn merge(&mut self, next: &LineEndingStats) {
+        self.crlf += next.crlf;
+        self.lf += next.lf;
+        self.cr += next.cr;
+        self.last = next.last.or(self.last);
+    }
+
+    /// Number of CRLF pairs (Windows line endings).
+    pub fn crlf(&self) -> u64 {
+        self.crlf
+    }
+
+    /// Number of LF bytes which are not part of CRLF pair (Unix line endings).
+    pub fn lf(&self) -> u64 {
+        self.lf
+    }
+
+    /// Number of CR bytes which are not part of CRLF pair (classic Mac OS line endings).
+    pub fn cr(&self) -> u64 {
+        self.cr
+    }
+
+    /// Total number of line endings of all kinds.
+    pub fn total(&self) -> u64 {
+        self.crlf + self.lf + self.cr
+    }
+
+    /// Returns true if more than one kind of line endings is used in the input.
+    pub fn is_mixed(&self) -> bool {
+        [self.crlf, self.lf, self.cr]
+            .iter()
+            .filter(|count| **count > 0)
+            .count()
+            > 1
+    }
+
+    /// Returns the most frequent kind of line endings, or `None` if there are no line endings at
+    /// all. Ties are resolved in favor of LF, then CRLF and then CR.
+    pub fn dominant(&self) -> Option<TransformMode> {
+        if self.total() == 0 {
+            None
+        } else if self.lf >= self.crlf && self.lf >= self.cr {
+            Some(TransformMode::Lf)
+        } else if self.crlf >= self.cr {
+            Some(TransformMode::Crlf)
+        } else {
+            Some(TransformMode::Cr)
+        }
+    }
+
+    /// Returns true if the input is terminated by a line ending. Empty input is not.
+    pub fn ends_with_newline(&self) -> bool {
+        self.last == Some(LF_CHAR) || self.last == Some(CR_CHAR)
+    }
+}
+
+/// Analyzes line endings used in the given input without converting it. The input is read in the
+/// same buffered manner as in [process](fn.process.html).
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use loe::analyze;
+///
+/// let mut input = Cursor::new("hello\nworld!");
+/// let stats = analyze(&mut input).unwrap();
+///
+/// assert_eq!(stats.lf(), 1);
+/// assert!(!stats.is_mixed());
+/// assert!(!stats.ends_with_newline());
+/// ```
+pub fn analyze<I: Read>(input: &mut I) -> Result<LineEndingStats, ParseError> {
+    let mut stats = LineEndingStats::new();
+    let mut read_buffer = vec![0; DEFAULT_BUFFER_SIZE];
+
+    loop {
+        match input.read(&mut read_buffer) {
+            Ok(0) => break,
+            Ok(n) => stats.feed(&read_buffer[0..n]),
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(ParseError::read_error(err)),
+        }
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(input: &[u8]) -> LineEndingStats {
+        let mut stats = LineEndingStats::new();
+        stats.feed(input);
+        stats
+    }
+
+    #[test]
+    fn counts() {
+        let stats = stats(b"a\r\nb\nc\rd\r\r\n\n");
+        assert_eq!(stats.crlf(), 2);
+        assert_eq!(stats.lf(), 2);
+        assert_eq!(stats.cr(), 2);
+        assert_eq!(stats.total(), 6);
+        assert!(stats.is_mixed());
+        assert!(stats.ends_with_newline());
+    }
+
+    #[test]
+    fn split_pair() {
+        let mut stats = LineEndingStats::new();
+        stats.feed(b"hello\r");
+        assert_eq!(stats.cr(), 1);
+        stats.feed(b"\nworld!\r");
+        assert_eq!(stats.crlf(), 1);
+        assert_eq!(stats.cr(), 1);
+        stats.feed(b"\n");
+        assert_eq!(stats.crlf(), 2);
+        assert_eq!(stats.cr(), 0);
+        assert!(!stats.is_mixed());
+        assert!(stats.ends_with_newline());
+    }
+
+    #[test]
+    fn dominant() {
+        assert_eq!(stats(b"hello").dominant(), None);
+        assert_eq!(stats(b"a\nb\r\nc\n").dominant(), Some(TransformMode::Lf));
+        assert_eq!(
+            stats(b"a\r\nb\nc\r\n").dominant(),
+            Some(TransformMode::Crlf)
+        );
+        assert_eq!(stats(b"a\rb\rc\r\n").dominant(), Some(TransformMode::Cr));
+        assert_eq!(stats(b"a\nb\r\n").dominant(), Some(TransformMode::Lf));
+        assert_eq!(stats(b"a\rb\r\n").dominant(), Some(TransformMode::Crlf));
+    }
+
+    #[test]
+    fn empty() {
+        let stats = stats(b"");
+        assert_eq!(stats.total(), 0);
+        assert!(!stats.is_mixed());
+        assert!(!stats.ends_with_newline());
+    }
+}
@@ -16,8 +16,13 @@
 //! assert_eq!(actual, expected);
 //! ```
 
-const LF_CHAR: u8 = 0x0a;
-const CR_CHAR: u8 = 0x0d;
+use std::convert::TryInto;
+use std::mem;
+
+use crate::{CodeUnit, LineEndingStats};
+
+pub(crate) const LF_CHAR: u8 = 0x0a;
+pub(crate) const CR_CHAR: u8 = 0x0d;
 
 /// Enumeration of possible transforms.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -26,6 +31,57 @@ pub enum TransformMode {
     Crlf,
     /// Unix line ending.
     Lf,
+    /// Classic Mac OS line ending.
+    Cr,
+    /// The line ending which dominates in the beginning of the input, the others are converted to
+    /// it. The size of the beginning is set by
+    /// [Config::lookahead](struct.Config.html#method.lookahead). If there are no line endings in
+    /// it, LF is used. Ties are resolved the same way as in
+    /// [LineEndingStats::dominant](struct.LineEndingStats.html#method.dominant).
+    Auto,
+    /// The line ending of the target platform, CRLF on Windows and LF elsewhere. See
+    /// [native](#method.native) and [native_or](#method.native_or).
+    Native,
+}
+
+impl TransformMode {
+    /// Returns the line ending which [Native](#variant.Native) resolves to, that is, CRLF on
+    /// Windows and LF elsewhere.
+    ///
+    /// ```
+    /// use loe::TransformMode;
+    ///
+    /// let expected = if cfg!(windows) { TransformMode::Crlf } else { TransformMode::Lf };
+    /// assert_eq!(TransformMode::native(), expected);
+    /// ```
+    pub fn native() -> TransformMode {
+        if cfg!(windows) {
+            TransformMode::Crlf
+        } else {
+            TransformMode::Lf
+        }
+    }
+
+    /// Returns the explicitly requested line ending if there is one, otherwise the native line
+    /// ending. It is useful when the native line ending can be overridden by the user.
+    ///
+    /// Any mode other than `Native` is returned as is, including `Auto`, so the result is a
+    /// concrete line ending only if the requested one is. Callers which need a concrete line ending
+    /// must validate the override themselves.
+    ///
+    /// ```
+    /// use loe::TransformMode;
+    ///
+    /// assert_eq!(TransformMode::native_or(Some(TransformMode::Cr)), TransformMode::Cr);
+    /// assert_eq!(TransformMode::native_or(None), TransformMode::native());
+    /// assert_eq!(TransformMode::native_or(Some(TransformMode::Auto)), TransformMode::Auto);
+    /// ```
+    pub fn native_or(mode: Option<TransformMode>) -> TransformMode {
+        match mode {
+            Some(TransformMode::Native) | None => TransformMode::native(),
+            Some(mode) => mode,
+        }
+    }
 }
 
 impl From<TransformMode> for Box<dyn Transform> {
@@ -33,6 +89,9 @@ impl From<TransformMode> for Box<dyn Transform> {
         match val {
             TransformMode::Crlf => Box::new(Crlf::new()),
             TransformMode::Lf => Box::new(Lf::new()),
+            TransformMode::Cr => Box::new(Cr::new()),
+            TransformMode::Auto => Box::new(Auto::new()),
+            TransformMode::Native => TransformMode::native().into(),
         }
     }
 }
@@ -51,13 +110,149 @@ pub trait Transform {
         input: &[u8],
         output: &mut [u8],
     ) -> usize;
+
+    /// Transforms the whole input slice to the beginning of the output and returns the number of
+    /// written bytes. The output must be at least twice as long as the input. The default
+    /// implementation calls [transform_buffer](#tymethod.transform_buffer) for every byte,
+    /// transforms should override it if they can process larger chunks at once.
+    fn transform_slice(&mut self, input: &[u8], output: &mut [u8]) -> usize {
+        let mut out_ptr = 0;
+        for in_ptr in 0..input.len() {
+            out_ptr = self.transform_buffer(in_ptr, out_ptr, input, output);
+        }
+
+        out_ptr
+    }
+
+    /// Returns the line ending which the transform writes for every line ending in the input, if it
+    /// is one of the known ones. It is used for counting the converted line endings in the
+    /// [Report](struct.Report.html).
+    fn target(&self) -> Option<TransformMode> {
+        None
+    }
+
+    /// Returns true if the transform needs to see the beginning of the input before transforming
+    /// it. The beginning is then passed to [prepare](#method.prepare).
+    fn needs_lookahead(&self) -> bool {
+        false
+    }
+
+    /// Prepares the transform using the beginning of the input. It is called only if
+    /// [needs_lookahead](#method.needs_lookahead) returns true, before any transforming. The
+    /// beginning is then transformed as usual.
+    fn prepare(&mut self, _head: &[u8]) {}
+}
+
+/// Writes given line ending sequence for every line ending in the input. All of CRLF pair, lone LF
+/// and lone CR are recognized as a line ending. The sequence is written as soon as CR is
+/// encountered and the flag `after_cr` is set so that a directly following LF is skipped.
+fn replace_ending(
+    ending: &[u8],
+    after_cr: &mut bool,
+    in_ptr: usize,
+    mut out_ptr: usize,
+    input: &[u8],
+    output: &mut [u8],
+) -> usize {
+    let byte = input[in_ptr];
+
+    if byte == LF_CHAR && *after_cr {
+        *after_cr = false;
+        return out_ptr;
+    }
+
+    *after_cr = byte == CR_CHAR;
+
+    if byte == CR_CHAR || byte == LF_CHAR {
+        output[out_ptr..out_ptr + ending.len()].copy_from_slice(ending);
+        out_ptr += ending.len();
+    } else {
+        output[out_ptr] = byte;
+        out_ptr += 1;
+    }
+
+    out_ptr
+}
+
+/// Slice version of [replace_ending](fn.replace_ending.html). The parts between line endings are
+/// copied at once.
+fn replace_endings(ending: &[u8], after_cr: &mut bool, input: &[u8], output: &mut [u8]) -> usize {
+    if input.is_empty() {
+        return 0;
+    }
+
+    let mut in_ptr = 0;
+    let mut out_ptr = 0;
+
+    if *after_cr && input[0] == LF_CHAR {
+        in_ptr = 1;
+    }
+
+    *after_cr = false;
+
+    while let Some(pos) = find_line_ending(&input[in_ptr..]) {
+        let end = in_ptr + pos;
+        let len = end - in_ptr;
+
+        output[out_ptr..out_ptr + len].copy_from_slice(&input[in_ptr..end]);
+        out_ptr += len;
+        output[out_ptr..out_ptr + ending.len()].copy_from_slice(ending);
+        out_ptr += ending.len();
+
+        in_ptr = end + 1;
+
+        if input[end] == CR_CHAR {
+            match input.get(in_ptr) {
+                Some(&LF_CHAR) => in_ptr += 1,
+                Some(_) => {}
+                None => *after_cr = true,
+            }
+        }
+    }
+
+    let len = input.len() - in_ptr;
+    output[out_ptr..out_ptr + len].copy_from_slice(&input[in_ptr..]);
+    out_ptr + len
+}
+
+const WORD_SIZE: usize = mem::size_of::<usize>();
+const LO_BITS: usize = usize::MAX / 0xff;
+const HI_BITS: usize = LO_BITS * 0x80;
+
+/// Returns the index of the first LF or CR in the bytes. It checks whole words at once, which is
+/// what memchr does too.
+pub(crate) fn find_line_ending(bytes: &[u8]) -> Option<usize> {
+    let lf = LO_BITS * LF_CHAR as usize;
+    let cr = LO_BITS * CR_CHAR as usize;
+
+    let mut start = 0;
+    while start + WORD_SIZE <= bytes.len() {
+        let word = usize::from_ne_bytes(bytes[start..start + WORD_SIZE].try_into().unwrap());
+
+        if contains_zero_byte(word ^ lf) || contains_zero_byte(word ^ cr) {
+            break;
+        }
+
+        start += WORD_SIZE;
+    }
+
+    bytes[start..]
+        .iter()
+        .position(|byte| *byte == LF_CHAR || *byte == CR_CHAR)
+        .map(|pos| start + pos)
 }
 
-struct Crlf;
+fn contains_zero_byte(word: usize) -> bool {
+    word.wrapping_sub(LO_BITS) & !word & HI_BITS != 0
+}
+
+struct Crlf {
+    after_cr: bool,
+}
 
 impl Crlf {
     fn new() -> Self {
-        Crlf
+        Crlf { after_cr: false }
     }
 }
 
@@ -65,33 +260,230 @@ impl Transform for Crlf {
     fn transform_buffer(
         &mut self,
         in_ptr: usize,
-        mut out_ptr: usize,
+        out_ptr: usize,
         input: &[u8],
         output: &mut [u8],
     ) -> usize {
-        if input[in_ptr] != CR_CHAR {
-            if input[in_ptr] == LF_CHAR {
-                output[out_ptr] = CR_CHAR;
-                out_ptr += 1;
-            }
+        replace_ending(
+            &[CR_CHAR, LF_CHAR],
+            &mut self.after_cr,
+            in_ptr,
+            out_ptr,
+            input,
+            output,
+        )
+    }
 
-            output[out_ptr] = input[in_ptr];
-            out_ptr += 1;
-        }
+    fn transform_slice(&mut self, input: &[u8], output: &mut [u8]) -> usize {
+        replace_endings(&[CR_CHAR, LF_CHAR], &mut self.after_cr, input, output)
+    }
 
-        out_ptr
+    fn target(&self) -> Option<TransformMode> {
+        Some(TransformMode::Crlf)
     }
 }
 
-struct Lf;
+struct Lf {
+    after_cr: bool,
+}
 
 impl Lf {
     fn new() -> Self {
-        Lf
+        Lf { after_cr: false }
     }
 }
 
 impl Transform for Lf {
+    fn transform_buffer(
+        &mut self,
+        in_ptr: usize,
+        out_ptr: usize,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> usize {
+        replace_ending(
+            &[LF_CHAR],
+            &mut self.after_cr,
+            in_ptr,
+            out_ptr,
+            input,
+            output,
+        )
+    }
+
+    fn transform_slice(&mut self, input: &[u8], output: &mut [u8]) -> usize {
+        replace_endings(&[LF_CHAR], &mut self.after_cr, input, output)
+    }
+
+    fn target(&self) -> Option<TransformMode> {
+        Some(TransformMode::Lf)
+    }
+}
+
+struct Cr {
+    after_cr: bool,
+}
+
+impl Cr {
+    fn new() -> Self {
+        Cr { after_cr: false }
+    }
+}
+
+impl Transform for Cr {
+    fn transform_buffer(
+        &mut self,
+        in_ptr: usize,
+        out_ptr: usize,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> usize {
+        replace_ending(
+            &[CR_CHAR],
+            &mut self.after_cr,
+            in_ptr,
+            out_ptr,
+            input,
+            output,
+        )
+    }
+
+    fn transform_slice(&mut self, input: &[u8], output: &mut [u8]) -> usize {
+        replace_endings(&[CR_CHAR], &mut self.after_cr, input, output)
+    }
+
+    fn target(&self) -> Option<TransformMode> {
+        Some(TransformMode::Cr)
+    }
+}
+
+/// Transform which decides the line ending from the beginning of the input and then delegates to
+/// the transform writing it.
+struct Auto {
+    inner: Option<Box<dyn Transform>>,
+}
+
+impl Auto {
+    fn new() -> Self {
+        Auto { inner: None }
+    }
+
+    fn inner(&mut self) -> &mut Box<dyn Transform> {
+        self.inner.get_or_insert_with(|| TransformMode::Lf.into())
+    }
+}
+
+impl Transform for Auto {
+    fn transform_buffer(
+        &mut self,
+        in_ptr: usize,
+        out_ptr: usize,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> usize {
+        self.inner()
+            .transform_buffer(in_ptr, out_ptr, input, output)
+    }
+
+    fn transform_slice(&mut self, input: &[u8], output: &mut [u8]) -> usize {
+        self.inner().transform_slice(input, output)
+    }
+
+    fn target(&self) -> Option<TransformMode> {
+        self.inner.as_ref().and_then(|inner| inner.target())
+    }
+
+    fn needs_lookahead(&self) -> bool {
+        true
+    }
+
+    fn prepare(&mut self, head: &[u8]) {
+        let mut stats = LineEndingStats::new();
+        stats.feed(head);
+        self.inner = Some(stats.dominant().unwrap_or(TransformMode::Lf).into());
+    }
+}
+
+/// Byte which represents all code units other than line endings for the inner transform of
+/// [Utf16Units](struct.Utf16Units.html).
+const PLACEHOLDER: u8 = b'x';
+
+/// Reader of UTF-16 code units from a stream of bytes. Code units of line endings are narrowed to
+/// single bytes and all other code units to a placeholder byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Utf16Narrower {
+    code_unit: CodeUnit,
+    big_endian: Option<bool>,
+    /// The first byte of the current code unit.
+    first: Option<u8>,
+}
+
+impl Utf16Narrower {
+    pub fn new(code_unit: CodeUnit) -> Self {
+        Utf16Narrower {
+            code_unit,
+            big_endian: None,
+            first: None,
+        }
+    }
+
+    /// Feeds next byte of the input. Once a code unit is complete, it returns the narrowed byte
+    /// and the original bytes of the code unit.
+    pub fn push(&mut self, byte: u8) -> Option<(u8, [u8; 2])> {
+        let first = match self.first.take() {
+            Some(first) => first,
+            None => {
+                self.first = Some(byte);
+                return None;
+            }
+        };
+
+        let bytes = [first, byte];
+        let code_unit = self.code_unit;
+        let big_endian = *self
+            .big_endian
+            .get_or_insert_with(|| code_unit.is_big_endian(bytes));
+
+        let unit = if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        };
+
+        let narrow = if unit == u16::from(LF_CHAR) || unit == u16::from(CR_CHAR) {
+            unit as u8
+        } else {
+            PLACEHOLDER
+        };
+
+        Some((narrow, bytes))
+    }
+
+    /// Returns true if the input is big-endian. It is decided by the first code unit.
+    pub fn is_big_endian(&self) -> bool {
+        self.big_endian.unwrap_or(false)
+    }
+}
+
+/// Adapter which makes a byte-oriented transform work on UTF-16 input. Code units of line endings
+/// are passed to the inner transform as single bytes and the bytes it writes are encoded back as
+/// code units. All other code units are represented by a placeholder byte and copied to the output
+/// as they are.
+pub(crate) struct Utf16Units {
+    inner: Box<dyn Transform>,
+    units: Utf16Narrower,
+}
+
+impl Utf16Units {
+    pub fn new(inner: Box<dyn Transform>, code_unit: CodeUnit) -> Self {
+        Utf16Units {
+            inner,
+            units: Utf16Narrower::new(code_unit),
+        }
+    }
+}
+
+impl Transform for Utf16Units {
     fn transform_buffer(
         &mut self,
         in_ptr: usize,
@@ -99,13 +491,47 @@ impl Transform for Lf {
         input: &[u8],
         output: &mut [u8],
     ) -> usize {
-        if input[in_ptr] != CR_CHAR {
-            output[out_ptr] = input[in_ptr];
-            out_ptr += 1;
+        let (narrow, bytes) = match self.units.push(input[in_ptr]) {
+            Some(unit) => unit,
+            None => return out_ptr,
+        };
+
+        let big_endian = self.units.is_big_endian();
+        let mut narrowed = [0; 4];
+        let len = self.inner.transform_buffer(0, 0, &[narrow], &mut narrowed);
+
+        for &narrow in &narrowed[0..len] {
+            let bytes = if narrow == PLACEHOLDER {
+                bytes
+            } else if big_endian {
+                [0, narrow]
+            } else {
+                [narrow, 0]
+            };
+
+            output[out_ptr..out_ptr + 2].copy_from_slice(&bytes);
+            out_ptr += 2;
         }
 
         out_ptr
     }
+
+    fn target(&self) -> Option<TransformMode> {
+        self.inner.target()
+    }
+
+    fn needs_lookahead(&self) -> bool {
+        self.inner.needs_lookahead()
+    }
+
+    fn prepare(&mut self, head: &[u8]) {
+        let mut units = Utf16Narrower::new(self.units.code_unit);
+        let narrowed = head
+            .iter()
+            .filter_map(|&byte| units.push(byte).map(|(narrow, _)| narrow))
+            .collect::<Vec<_>>();
+        self.inner.prepare(&narrowed);
+    }
 }
 
 #[cfg(test)]
@@ -132,11 +558,175 @@ mod tests {
             b"Hello\r\nworld!\r\n",
             b"Hello\r\nworld!\r\n",
         );
+        test(&mut Crlf::new(), b"Hello\rworld!\r", b"Hello\r\nworld!\r\n");
     }
 
     #[test]
     fn lf_basic() {
         test(&mut Lf::new(), b"Hello\r\nworld!\r\n", b"Hello\nworld!\n");
         test(&mut Lf::new(), b"Hello\nworld!\n", b"Hello\nworld!\n");
+        test(&mut Lf::new(), b"Hello\rworld!\r", b"Hello\nworld!\n");
+    }
+
+    #[test]
+    fn cr_basic() {
+        test(&mut Cr::new(), b"Hello\r\nworld!\r\n", b"Hello\rworld!\r");
+        test(&mut Cr::new(), b"Hello\nworld!\n", b"Hello\rworld!\r");
+        test(&mut Cr::new(), b"Hello\rworld!\r", b"Hello\rworld!\r");
+    }
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn utf16_units() {
+        for &(code_unit, big_endian) in &[
+            (CodeUnit::Utf16Le, false),
+            (CodeUnit::Utf16Be, true),
+            (CodeUnit::Utf16, false),
+        ] {
+            test(
+                &mut Utf16Units::new(Box::new(Lf::new()), code_unit),
+                &utf16("Ahoj\r\nsvěte!\rx\u{d0a}\u{a0d}\n", big_endian),
+                &utf16("Ahoj\nsvěte!\nx\u{d0a}\u{a0d}\n", big_endian),
+            );
+            test(
+                &mut Utf16Units::new(Box::new(Crlf::new()), code_unit),
+                &utf16("Hello\nworld!\r\n", big_endian),
+                &utf16("Hello\r\nworld!\r\n", big_endian),
+            );
+        }
+    }
+
+    #[test]
+    fn utf16_bom() {
+        let mut input = vec![0xfe, 0xff];
+        input.extend(utf16("a\r\nb", true));
+        let mut expected = vec![0xfe, 0xff];
+        expected.extend(utf16("a\nb", true));
+
+        test(
+            &mut Utf16Units::new(Box::new(Lf::new()), CodeUnit::Utf16),
+            &input,
+            &expected,
+        );
+    }
+
+    #[test]
+    fn native() {
+        let platform = if cfg!(windows) {
+            TransformMode::Crlf
+        } else {
+            TransformMode::Lf
+        };
+
+        assert_eq!(TransformMode::native(), platform);
+        assert_eq!(TransformMode::native_or(None), platform);
+        assert_eq!(
+            TransformMode::native_or(Some(TransformMode::Native)),
+            platform
+        );
+        assert_eq!(
+            TransformMode::native_or(Some(TransformMode::Crlf)),
+            TransformMode::Crlf
+        );
+    }
+
+    #[test]
+    fn auto() {
+        let mut auto = Auto::new();
+        auto.prepare(b"a\r\nb\r\nc\n");
+        assert_eq!(auto.target(), Some(TransformMode::Crlf));
+        test(&mut auto, b"a\nb\rc\r\n", b"a\r\nb\r\nc\r\n");
+
+        let mut auto = Auto::new();
+        auto.prepare(b"no line endings");
+        assert_eq!(auto.target(), Some(TransformMode::Lf));
+
+        let mut auto = Utf16Units::new(Box::new(Auto::new()), CodeUnit::Utf16Be);
+        auto.prepare(&utf16("a\rb\rc\r\n", true));
+        assert_eq!(auto.target(), Some(TransformMode::Cr));
+    }
+
+    #[test]
+    fn slice() {
+        let inputs: &[&[u8]] = &[
+            b"",
+            b"Hello world!",
+            b"Hello\r\nworld!\r\n",
+            b"\n\nHello\nworld!\n\n",
+            b"\r\rHello\rworld!\r\r",
+            b"a\r\r\nb\n\rc\r",
+            b"a long line which spans over several words\r\nand then another one\n",
+        ];
+
+        let transforms: &[fn() -> Box<dyn Transform>] = &[
+            || Box::new(Crlf::new()),
+            || Box::new(Lf::new()),
+            || Box::new(Cr::new()),
+        ];
+
+        for transform in transforms {
+            for input in inputs {
+                for split in 0..=input.len() {
+                    let mut expected = vec![0; input.len() * 2];
+                    let mut expected_transform = transform();
+                    let mut out_ptr = 0;
+                    for in_ptr in 0..input.len() {
+                        out_ptr = expected_transform.transform_buffer(
+                            in_ptr,
+                            out_ptr,
+                            input,
+                            &mut expected,
+                        );
+                    }
+                    expected.truncate(out_ptr);
+
+                    let mut actual = Vec::new();
+                    let mut actual_transform = transform();
+                    for part in &[&input[0..split], &[], &input[split..]] {
+                        let mut output = vec![0; part.len() * 2];
+                        let len = actual_transform.transform_slice(part, &mut output);
+                        actual.extend_from_slice(&output[0..len]);
+                    }
+
+                    assert_eq!(actual, expected, "{:?} {}", input, split);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn line_ending_search() {
+        assert_eq!(find_line_ending(b""), None);
+        assert_eq!(find_line_ending(b"abcdefghijklmnopqrstuvwxyz"), None);
+        for i in 0..20 {
+            let mut bytes = vec![b'a'; 20];
+            bytes[i] = LF_CHAR;
+            assert_eq!(find_line_ending(&bytes), Some(i));
+            bytes[i] = CR_CHAR;
+            assert_eq!(find_line_ending(&bytes), Some(i));
+        }
+        // bytes which differ from line endings by one bit only
+        assert_eq!(
+            find_line_ending(b"\x0b\x0c\x0e\x8a\x8d\x09\x08\x05\x02"),
+            None
+        );
+    }
+
+    #[test]
+    fn mixed() {
+        test(&mut Lf::new(), b"a\r\r\nb\n\rc", b"a\n\nb\n\nc");
+        test(&mut Crlf::new(), b"a\r\r\nb\n\rc", b"a\r\n\r\nb\r\n\r\nc");
+        test(&mut Cr::new(), b"a\r\r\nb\n\rc", b"a\r\rb\r\rc");
     }
 }